@@ -5,243 +5,4192 @@ extern crate syn;
 extern crate quote;
 
 use proc_macro::TokenStream;
-use syn::{Field, Ident, ItemStruct, Type, Visibility};
+use quote::ToTokens;
+use std::cell::RefCell;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Data, DeriveInput, Field, Fields, Generics, Ident, ItemStruct, LitStr, Member, Type,
+    Visibility, WherePredicate,
+};
 
-/// Represents a field that needs to get converted to a Mutable and back.
+thread_local! {
+    /// The paths that generated code uses to refer to the traits crate and to
+    /// futures-signals, which `#[signal_crate]` can change for the struct being derived.
+    static CRATE_PATHS: RefCell<(syn::Path, syn::Path)> = RefCell::new(default_crate_paths());
+}
+
+fn default_crate_paths() -> (syn::Path, syn::Path) {
+    (
+        syn::parse_quote!(::futures_signals_structs_traits),
+        syn::parse_quote!(::futures_signals),
+    )
+}
+
+/// Returns the path of the traits crate, for use in generated code.
+fn traits_crate() -> syn::Path {
+    CRATE_PATHS.with(|paths| paths.borrow().0.clone())
+}
+
+/// Returns the path of the futures-signals crate, for use in generated code.
+fn signals_crate() -> syn::Path {
+    CRATE_PATHS.with(|paths| paths.borrow().1.clone())
+}
+
+/// The names of Rust's primitive types, which are always treated as basic values.
+const PRIMITIVE_TYPES: &[&str] = &[
+    "bool", "char", "str", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16",
+    "u32", "u64", "u128", "usize",
+];
+
+/// Names of std types that are always treated as basic values, even though they start with
+/// an uppercase letter and would otherwise be mistaken for nested structs. Generic
+/// arguments and lifetimes don't matter, so `Cow<'a, str>` is basic as well.
+const KNOWN_BASIC_TYPES: &[&str] = &[
+    "String",
+    "Cow",
+    "Result",
+    "Duration",
+    "Instant",
+    "SystemTime",
+    "PathBuf",
+    "OsString",
+    "CString",
+    "IpAddr",
+    "Ipv4Addr",
+    "Ipv6Addr",
+    "SocketAddr",
+    "SocketAddrV4",
+    "SocketAddrV6",
+    "NonZeroU8",
+    "NonZeroU16",
+    "NonZeroU32",
+    "NonZeroU64",
+    "NonZeroU128",
+    "NonZeroUsize",
+    "NonZeroI8",
+    "NonZeroI16",
+    "NonZeroI32",
+    "NonZeroI64",
+    "NonZeroI128",
+    "NonZeroIsize",
+];
+
+/// Represents a field that needs to get converted to a Mutable and back. Fields of tuple
+/// structs are named by their position. `name` is the field's name on the mutable struct,
+/// and `source_name` its name on the original struct, which differ for renamed fields.
+/// Basic fields may store their value inside a smart pointer such as `Arc`, named by
+/// `wrapper`, or as a different type named by `store_as`, converted with the
+/// `snapshot_with` and `update_with` functions if given, may clamp incoming values to a
+/// `min` and `max`, and may compare values with a custom `eq_fn`. `signal_accessor` is the
+/// base name of the field's signal accessors, if it gets any. Nested fields whose
+/// `mutable_type` is a `MutableVec` of mutable structs name the element type in
+/// `element_type`, and nested fields whose type refers back to the struct itself are
+/// `recursive`. Nested fields gathered from several fields of the original struct with
+/// `#[signal_snapshot_flatten]` list them in `flattened`. The field's doc comments are kept
+/// in `docs`.
 enum MutableStructField {
     Basic {
-        name: Ident,
+        name: Member,
+        source_name: Member,
         vis: Visibility,
         ty: Type,
+        docs: Vec<Attribute>,
+        wrapper: Option<Box<syn::Path>>,
+        store_as: Option<Box<Type>>,
+        snapshot_with: Option<Box<syn::Path>>,
+        update_with: Option<Box<syn::Path>>,
+        min: Option<Box<syn::Expr>>,
+        max: Option<Box<syn::Expr>>,
+        eq_fn: Option<Box<syn::Path>>,
+        signal_accessor: Option<Ident>,
     },
     MutableStruct {
-        name: Ident,
+        name: Member,
+        source_name: Member,
+        vis: Visibility,
+        ty: Type,
+        docs: Vec<Attribute>,
+        mutable_type: Option<Box<Type>>,
+        element_type: Option<Box<Type>>,
+        signal_accessor: Option<Ident>,
+        recursive: bool,
+        flattened: Vec<Member>,
+    },
+    Skipped {
+        name: Member,
+        source_name: Member,
         vis: Visibility,
         ty: Type,
+        docs: Vec<Attribute>,
     },
 }
 
-impl From<&Field> for MutableStructField {
-    fn from(field: &Field) -> MutableStructField {
-        if MutableStructField::field_is_primitive(field) {
-            MutableStructField::Basic {
-                name: field.ident.clone().unwrap(),
+impl MutableStructField {
+    /// Creates a MutableStructField from the field at position `index` of the source struct.
+    /// Signal accessors are named `<field><accessor_suffix>` unless the field names its own.
+    pub fn new(
+        index: usize,
+        field: &Field,
+        accessor_suffix: &str,
+    ) -> syn::Result<MutableStructField> {
+        let mut field = field.clone();
+        ungroup_type(&mut field.ty);
+        let field = &field;
+        let source_name = field
+            .ident
+            .clone()
+            .map(Member::Named)
+            .unwrap_or_else(|| Member::Unnamed(index.into()));
+        let rename = maybe_get_rename(field)?;
+        if let (Some(rename), None) = (&rename, &field.ident) {
+            return Err(syn::Error::new_spanned(
+                rename,
+                "signal_rename can only be used on named fields.",
+            ));
+        }
+        let ident = rename.as_ref().or(field.ident.as_ref());
+        let name = ident
+            .cloned()
+            .map(Member::Named)
+            .unwrap_or_else(|| source_name.clone());
+        let signal_accessor = match maybe_get_signal_accessor(field)? {
+            Some(signal_accessor) => Some(signal_accessor),
+            None => ident.map(|ident| format_ident!("{}{}", ident, accessor_suffix)),
+        };
+
+        let docs = field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("doc"))
+            .cloned()
+            .collect::<Vec<Attribute>>();
+
+        if field_has_flag(field, "signal_skip") {
+            return Ok(MutableStructField::Skipped {
+                name,
+                source_name,
                 vis: field.vis.clone(),
                 ty: field.ty.clone(),
+                docs,
+            });
+        }
+
+        // Snapshots can't borrow from the mutable struct, so references are only supported
+        // through an owned twin of the struct, which needs to know what to store instead.
+        if let Type::Reference(reference) = &field.ty {
+            let owned_hint = match &*reference.elem {
+                Type::Path(path) if path.path.is_ident("str") => "`String`",
+                Type::Slice(_) => "a `Vec`",
+                _ => "the referenced type",
+            };
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                format!(
+                    "Reference fields need a #[signal_store_as] hint naming the owned type to store, such as {}.",
+                    owned_hint
+                ),
+            ));
+        }
+
+        let mut mutable_type = maybe_get_mutable_type(field)?;
+        let wrapper = maybe_get_wrapper(field)?;
+        if let (Some(_), Some(wrapper)) = (&mutable_type, &wrapper) {
+            return Err(syn::Error::new_spanned(
+                wrapper,
+                "signal_wrap cannot be combined with mutable_type.",
+            ));
+        }
+        let store_as = maybe_get_store_as(field)?;
+        match (&store_as, &mutable_type, &wrapper) {
+            (Some(_), Some(mutable_type), _) => {
+                return Err(syn::Error::new_spanned(
+                    mutable_type,
+                    "signal_store_as cannot be combined with mutable_type.",
+                ))
+            }
+            (Some(_), _, Some(wrapper)) => {
+                return Err(syn::Error::new_spanned(
+                    wrapper,
+                    "signal_store_as cannot be combined with signal_wrap.",
+                ))
+            }
+            _ => {}
+        }
+        let snapshot_with = maybe_get_function(field, "signal_snapshot_with")?;
+        let update_with = maybe_get_function(field, "signal_update_with")?;
+        match (&snapshot_with, &update_with) {
+            (Some(snapshot_with), None) => {
+                return Err(syn::Error::new_spanned(
+                    snapshot_with,
+                    "signal_snapshot_with must be combined with signal_update_with.",
+                ))
+            }
+            (None, Some(update_with)) => {
+                return Err(syn::Error::new_spanned(
+                    update_with,
+                    "signal_update_with must be combined with signal_snapshot_with.",
+                ))
+            }
+            (Some(snapshot_with), Some(_)) if store_as.is_none() => {
+                return Err(syn::Error::new_spanned(
+                    snapshot_with,
+                    "signal_snapshot_with requires signal_store_as to name the stored type.",
+                ))
+            }
+            _ => {}
+        }
+        let min = maybe_get_limit(field, "signal_min")?;
+        let max = maybe_get_limit(field, "signal_max")?;
+        let eq_fn = maybe_get_eq_fn(field)?;
+        if let (Some(_), Some(eq_fn)) = (&store_as, &eq_fn) {
+            return Err(syn::Error::new_spanned(
+                eq_fn,
+                "signal_eq_fn cannot be combined with signal_store_as.",
+            ));
+        }
+        let force_basic = field_has_flag(field, "signal_basic");
+        if let (true, Some(mutable_type)) = (force_basic, &mutable_type) {
+            return Err(syn::Error::new_spanned(
+                mutable_type,
+                "signal_basic cannot be combined with mutable_type.",
+            ));
+        }
+        if let Some(attr) = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("signal_lazy"))
+        {
+            if let Some(mutable_type) = &mutable_type {
+                return Err(syn::Error::new_spanned(
+                    mutable_type,
+                    "signal_lazy cannot be combined with mutable_type.",
+                ));
+            }
+            if force_basic
+                || wrapper.is_some()
+                || store_as.is_some()
+                || MutableStructField::field_is_primitive(field)
+            {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "signal_lazy can only be used on nested struct fields.",
+                ));
             }
+            let traits = traits_crate();
+            let ty = &field.ty;
+            mutable_type = Some(Box::new(syn::parse_quote!(
+                #traits::lazy::MutableLazy<<#ty as #traits::AsMutableStruct>::MutableStructType>
+            )));
+        }
+        if force_basic
+            || wrapper.is_some()
+            || store_as.is_some()
+            || (mutable_type.is_none() && MutableStructField::field_is_primitive(field))
+        {
+            Ok(MutableStructField::Basic {
+                name,
+                source_name,
+                vis: field.vis.clone(),
+                ty: field.ty.clone(),
+                docs,
+                wrapper,
+                store_as,
+                snapshot_with,
+                update_with,
+                min,
+                max,
+                eq_fn,
+                signal_accessor,
+            })
+        } else if let Some(eq_fn) = eq_fn {
+            Err(syn::Error::new_spanned(
+                eq_fn,
+                "signal_eq_fn can only be used on fields stored in a Mutable.",
+            ))
+        } else if let Some(limit) = min.or(max) {
+            Err(syn::Error::new_spanned(
+                limit,
+                "signal_min and signal_max can only be used on fields stored in a Mutable.",
+            ))
         } else {
-            MutableStructField::MutableStruct {
-                name: field.ident.clone().unwrap(),
+            let element_type = mutable_type
+                .as_deref()
+                .and_then(|mutable_type| {
+                    MutableStructField::struct_element_type(&field.ty, mutable_type)
+                })
+                .map(|element_type| Box::new(element_type.clone()));
+            Ok(MutableStructField::MutableStruct {
+                name,
+                source_name,
                 vis: field.vis.clone(),
                 ty: field.ty.clone(),
-            }
+                docs,
+                mutable_type,
+                element_type,
+                signal_accessor,
+                recursive: false,
+                flattened: vec![],
+            })
         }
     }
-}
 
-impl MutableStructField {
-    /// Returns a struct definition of the mutable version of this field.
-    pub fn get_mutable_field_definition(&self) -> proc_macro2::TokenStream {
+    /// Returns the element type of a `mutable_type` like `MutableVec<MutableT>` on a
+    /// `Vec<T>` field, whose elements are mutable structs rather than plain values.
+    fn struct_element_type<'a>(ty: &Type, mutable_type: &'a Type) -> Option<&'a Type> {
+        let last_segment = |ty: &Type| match ty {
+            Type::Path(type_path) => type_path.path.segments.last().cloned(),
+            _ => None,
+        };
+        let vec_segment = last_segment(ty).filter(|segment| segment.ident == "Vec")?;
+        let value_type = MutableStructField::first_type_argument(&vec_segment)?;
+        let mutable_segment = match mutable_type {
+            Type::Path(type_path) => type_path.path.segments.last()?,
+            _ => return None,
+        };
+        if mutable_segment.ident != "MutableVec" {
+            return None;
+        }
+        let element_type = MutableStructField::first_type_argument(mutable_segment)?;
+        if element_type.to_token_stream().to_string() == value_type.to_token_stream().to_string() {
+            None
+        } else {
+            Some(element_type)
+        }
+    }
+
+    /// Returns the trait that converts this nested field to and from its snapshot.
+    fn get_nested_trait(&self) -> proc_macro2::TokenStream {
+        let traits = traits_crate();
+        match self {
+            MutableStructField::MutableStruct {
+                element_type: Some(_),
+                ..
+            } => quote!(#traits::vec::MutableStructElements),
+            _ => quote!(#traits::MutableStruct),
+        }
+    }
+
+    /// Returns the type of this field on the mutable struct.
+    pub fn get_mutable_type(&self) -> proc_macro2::TokenStream {
+        let traits = traits_crate();
+        let signals = signals_crate();
         match self {
-            MutableStructField::Basic { vis, name, ty } => {
-                quote!(#vis #name: futures_signals::signal::Mutable<#ty>)
+            MutableStructField::Basic { .. } => {
+                let value_type = self.get_value_type();
+                quote!(#signals::signal::Mutable<#value_type>)
             }
             MutableStructField::MutableStruct {
-                vis,
-                name,
+                mutable_type: Some(mutable_type),
+                ..
+            } => quote!(#mutable_type),
+            MutableStructField::MutableStruct {
+                ty,
+                mutable_type: None,
+                ..
+            } => quote!(<#ty as #traits::AsMutableStruct>::MutableStructType),
+            MutableStructField::Skipped { ty, .. } => quote!(#ty),
+        }
+    }
+
+    /// Returns a struct definition of the mutable version of this field. Doc comments are
+    /// copied over, but other attributes on the original field, such as `#[serde(...)]`, are
+    /// not, since they may not apply to the mutable type.
+    pub fn get_mutable_field_definition(&self) -> proc_macro2::TokenStream {
+        let vis = self.get_vis();
+        let docs = self.get_docs();
+        let mutable_type = self.get_mutable_type();
+        match self.get_name() {
+            Member::Named(ident) => quote!(#(#docs)* #vis #ident: #mutable_type),
+            Member::Unnamed(_) => quote!(#(#docs)* #vis #mutable_type),
+        }
+    }
+
+    /// Returns the type of this field's current value, as used when printing or comparing
+    /// mutable structs. Basic fields expose the value inside their Mutable.
+    pub fn get_value_type(&self) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::Basic {
                 ty,
-            } => quote!(#vis #name: <#ty as futures_signals_structs_traits::AsMutableStruct>::MutableStructType),
+                wrapper: Some(wrapper),
+                ..
+            } => quote!(#wrapper<#ty>),
+            MutableStructField::Basic {
+                store_as: Some(store_as),
+                ..
+            } => quote!(#store_as),
+            MutableStructField::Basic { ty, .. } => quote!(#ty),
+            _ => self.get_mutable_type(),
+        }
+    }
+
+    /// Returns code that borrows the current value of this field on `receiver`.
+    pub fn get_value_ref(&self, receiver: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::Basic { name, .. } => quote!(&*#receiver.#name.lock_ref()),
+            MutableStructField::MutableStruct { name, .. } => quote!(&#receiver.#name),
+            MutableStructField::Skipped { name, .. } => quote!(&#receiver.#name),
         }
     }
 
     /// Returns code that can generate a constructor from a non-mutable version of the struct.
     pub fn get_constructor(&self, snapshot_name: Ident) -> proc_macro2::TokenStream {
+        let traits = traits_crate();
+        let signals = signals_crate();
         match self {
-            MutableStructField::Basic { name, .. } => {
-                quote!(futures_signals::signal::Mutable::new(#snapshot_name.#name))
+            MutableStructField::Basic { source_name, .. } => {
+                let value = self.wrap_value(quote!(#snapshot_name.#source_name.clone()));
+                quote!(#signals::signal::Mutable::new(#value))
             }
-            MutableStructField::MutableStruct { name, .. } => {
-                quote!(#snapshot_name.#name.as_mutable_struct())
+            MutableStructField::MutableStruct {
+                mutable_type: Some(mutable_type),
+                ..
+            } => {
+                let nested_trait = self.get_nested_trait();
+                let value = self.get_snapshot_value_ref(quote!(#snapshot_name));
+                quote!(<#mutable_type as #nested_trait>::from_snapshot(#value))
+            }
+            MutableStructField::MutableStruct { .. } => {
+                let value = self.get_snapshot_value_ref(quote!(#snapshot_name));
+                quote!(#traits::AsMutableStruct::as_mutable_struct(#value))
+            }
+            MutableStructField::Skipped { source_name, .. } => {
+                quote!(#snapshot_name.#source_name.clone())
+            }
+        }
+    }
+
+    /// Returns code that converts `value`, an owned value of this field's original type,
+    /// into the field of the mutable struct.
+    pub fn get_value_constructor(
+        &self,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let traits = traits_crate();
+        let signals = signals_crate();
+        match self {
+            MutableStructField::Basic { .. } => {
+                let value = self.wrap_value(value);
+                quote!(#signals::signal::Mutable::new(#value))
+            }
+            MutableStructField::MutableStruct {
+                mutable_type: Some(mutable_type),
+                ..
+            } => {
+                let nested_trait = self.get_nested_trait();
+                quote!(<#mutable_type as #nested_trait>::from_snapshot(&#value))
             }
+            MutableStructField::MutableStruct { .. } => {
+                quote!(#traits::AsMutableStruct::as_mutable_struct(&#value))
+            }
+            MutableStructField::Skipped { .. } => value,
         }
     }
 
     /// Returns code that gets a static version of this field.
     pub fn get_snapshot_generator(&self) -> proc_macro2::TokenStream {
         match self {
+            MutableStructField::Basic { name, .. } if self.is_converted() => {
+                self.unwrap_value(quote!(&*self.#name.lock_ref()))
+            }
             MutableStructField::Basic { name, .. } => quote!(self.#name.get_cloned()),
-            MutableStructField::MutableStruct { name, .. } => quote!(self.#name.snapshot()),
+            MutableStructField::MutableStruct { name, .. } => {
+                let nested_trait = self.get_nested_trait();
+                quote!(#nested_trait::snapshot(&self.#name))
+            }
+            MutableStructField::Skipped { name, .. } => quote!(self.#name.clone()),
         }
     }
 
-    /// Returns code that updates the mutable value from a non-mutable version of this struct.
-    pub fn get_update_setter(&self, snapshot_name: Ident) -> proc_macro2::TokenStream {
+    /// Returns code that writes this field's snapshot into the same field of `out`, reusing
+    /// the allocations of its current value where possible.
+    pub fn get_snapshot_into(&self) -> proc_macro2::TokenStream {
+        let traits = traits_crate();
         match self {
-            MutableStructField::Basic { name, .. } => quote!(self.#name.set(#snapshot_name.#name)),
-            MutableStructField::MutableStruct { name, .. } => {
-                quote!(self.#name.update(#snapshot_name.#name))
+            MutableStructField::Basic {
+                source_name,
+                store_as: Some(_),
+                ..
+            } => {
+                let snapshot_generator = self.get_snapshot_generator();
+                quote!(out.#source_name = #snapshot_generator)
+            }
+            MutableStructField::Basic { source_name, .. } => {
+                let current = self.get_current_value_ref();
+                quote!(::std::clone::Clone::clone_from(&mut out.#source_name, #current))
+            }
+            MutableStructField::MutableStruct {
+                name,
+                source_name,
+                element_type: Some(_),
+                ..
+            } => {
+                let nested_trait = self.get_nested_trait();
+                quote!(out.#source_name = #nested_trait::snapshot(&self.#name))
+            }
+            MutableStructField::MutableStruct {
+                name, flattened, ..
+            } if !flattened.is_empty() => {
+                let nested_trait = self.get_nested_trait();
+                let binding = self.get_binding();
+                quote!({
+                    let #binding = #nested_trait::snapshot(&self.#name);
+                    #(out.#flattened = #binding.#flattened;)*
+                })
+            }
+            MutableStructField::MutableStruct {
+                name, source_name, ..
+            } => quote!(
+                #traits::MutableStruct::snapshot_into(&self.#name, &mut out.#source_name)
+            ),
+            MutableStructField::Skipped {
+                name, source_name, ..
+            } => {
+                quote!(::std::clone::Clone::clone_from(&mut out.#source_name, &self.#name))
             }
         }
     }
 
-    /// Returns the name of this field as an ident.
-    pub fn get_name(&self) -> &proc_macro2::Ident {
+    /// Returns code that creates an independent copy of this field on `self`. Basic fields
+    /// get a new Mutable holding a clone of the current value, while nested structs are
+    /// copied through their snapshot, which works for any MutableStruct.
+    pub fn get_cloner(&self) -> proc_macro2::TokenStream {
+        let signals = signals_crate();
         match self {
-            MutableStructField::Basic { name, .. } => name,
-            MutableStructField::MutableStruct { name, .. } => name,
+            MutableStructField::Basic { name, .. } => {
+                quote!(#signals::signal::Mutable::new(self.#name.get_cloned()))
+            }
+            MutableStructField::MutableStruct { name, .. } => {
+                let mutable_type = self.get_mutable_type();
+                let nested_trait = self.get_nested_trait();
+                quote!(<#mutable_type as #nested_trait>::from_snapshot(&#nested_trait::snapshot(&self.#name)))
+            }
+            MutableStructField::Skipped { name, .. } => quote!(self.#name.clone()),
         }
     }
 
-    /// Returns the value of the mutable_type annotation, if specified.
-    fn field_is_primitive(input: &Field) -> bool {
-        if let Type::Path(type_path) = &input.ty {
-            let last_component = type_path.path.segments.last().unwrap();
-            let name = last_component.ident.to_string();
-            name.chars().nth(0).unwrap().is_ascii_lowercase()
-        } else {
-            false
+    /// Returns code that updates the mutable value from a non-mutable version of this struct.
+    /// Skipped fields are not stored in a Mutable, so `update` has no way to change them
+    /// and they keep the value they were constructed with.
+    pub fn get_update_setter(&self, snapshot_name: Ident) -> Option<proc_macro2::TokenStream> {
+        match self {
+            MutableStructField::Basic {
+                name, source_name, ..
+            } => {
+                let value = self.wrap_value(quote!(#snapshot_name.#source_name));
+                Some(quote!(self.#name.set(#value)))
+            }
+            MutableStructField::MutableStruct { name, .. } => {
+                let nested_trait = self.get_nested_trait();
+                let value = self.get_snapshot_value(quote!(#snapshot_name));
+                Some(quote!(#nested_trait::update(&self.#name, #value)))
+            }
+            MutableStructField::Skipped { .. } => None,
         }
     }
-}
-
-/// Derives a function called `as_mutable_struct()` that returns a version of the struct
-/// where all fields are Mutable objects.
-/// ```
-///     #[derive(AsMutableStruct)]
-///     struct PlayerScore {
-///         hits: u32,
-///         multiplier: f32,
-///     }
-/// 
-///     fn main() {
-///         let score = PlayerScore {
-///             hits: 4,
-///             multiplier: 0.4,
-///         };
-///         let mutable_score = score.as_mutable_struct();
-///         mutable_score.hits.set(6);
-///         mutable_score.multiplier.set(0.5);
-///     }
-/// ```
-/// By default this creates a new struct called MutablePlayerScore that can also be
-/// constructed directly as necessary.
-/// ```
-///     let mutable_score = MutablePlayerScore {
-///         hits: Mutable::new(5),
-///         multiplier: Mutable::new(1.4),
-///     };
-/// ```
-/// Either way you construct it, the mutable object can be 'snapshotted' into the
-/// original struct.
-/// ```
-///     assert_eq!(mutable_score.snapshot(), PlayerScore {
-///         hits: 5,
-///         multiplier: 1.4,
-///     });
-/// ```
-/// The mutable value can also be updated to match a new static struct.
-/// ```
-///     mutable_score.update(PlayerScore {
-///         hits: 50,
-///         multiplier: 15,
-///     });
-///     assert_eq!(mutable_score.snapshot(), PlayerScore {
-///         hits: 50,
-///         multiplier: 15,
-///     });
-/// ```
-/// Structs can depend on other structs when annotated with #[mutable_type]
-/// ```
-///     #[derive(AsMutableStruct)]
-///     struct GameScore {
-///         #[mutable_type = "MutablePlayerScore"] player_1: PlayerScore,
-///         #[mutable_type = "MutablePlayerScore"] player_2: PlayerScore,
-///     }
-/// ```
-#[proc_macro_derive(AsMutableStruct, attributes(MutableStructName))]
-pub fn as_mutable_struct(input: TokenStream) -> TokenStream {
-    // Parse the string representation
-    let ast: ItemStruct = syn::parse_macro_input!(input);
-
-    // Determine what to name the Mutable version of this struct. Tries to pull from the
-    // MutableStructName attribute, falls back to `MutableStructName` where StructName
-    // is the name of the derived struct.
-    let mutable_name = maybe_get_mutable_name(ast.clone())
-        .map(|name| format_ident!("{}", name))
-        .or(Some(format_ident!("Mutable{}", &ast.ident)))
-        .unwrap();
-
-    // Extract all fields as MutableStructField instances.
-    let fields = ast.fields.iter().map(MutableStructField::from).collect();
-
-    // Build the impl
-    let gen_mutable = make_mutable_variant(ast.clone(), &fields, &mutable_name);
-    let gen_as_signal_struct = impl_as_signal_struct(ast, &fields, &mutable_name);
-
-    // Return the generated impl
-    quote!(#gen_mutable #gen_as_signal_struct).into()
-}
-
-fn make_mutable_variant(
-    input: ItemStruct,
-    fields: &Vec<MutableStructField>,
-    mutable_name: &Ident,
-) -> proc_macro2::TokenStream {
-    let original_ident = input.ident;
-    let original_vis = input.vis;
-
-    let mutable_fields = fields
-        .iter()
-        .map(MutableStructField::get_mutable_field_definition)
-        .collect::<Vec<proc_macro2::TokenStream>>();
-
-    let snapshot_fields = fields
-        .iter()
-        .map(|field| {
-            let name = field.get_name();
-            let snapshot_generator = field.get_snapshot_generator();
-            quote!(#name: #snapshot_generator)
-        })
-        .collect::<Vec<proc_macro2::TokenStream>>();
-
-    let update_fields = fields
-        .iter()
-        .map(|field| field.get_update_setter(format_ident!("new_snapshot")))
-        .collect::<Vec<proc_macro2::TokenStream>>();
 
-    quote! {
-        #original_vis struct #mutable_name {
-            #(#mutable_fields),*
+    /// Returns code that updates the mutable value from a borrowed non-mutable version of this
+    /// struct, cloning only the values that have to be stored. Nested structs are updated
+    /// through their own `update_ref`, apart from vectors of structs, which take their
+    /// elements by value.
+    pub fn get_update_ref_setter(&self, snapshot_name: Ident) -> Option<proc_macro2::TokenStream> {
+        let traits = traits_crate();
+        match self {
+            MutableStructField::Basic {
+                name, source_name, ..
+            } => {
+                let value = self
+                    .wrap_value(quote!(::std::clone::Clone::clone(&#snapshot_name.#source_name)));
+                Some(quote!(self.#name.set(#value)))
+            }
+            MutableStructField::MutableStruct {
+                name,
+                source_name,
+                element_type: Some(_),
+                ..
+            } => {
+                let nested_trait = self.get_nested_trait();
+                Some(quote!(#nested_trait::update(
+                    &self.#name,
+                    ::std::clone::Clone::clone(&#snapshot_name.#source_name),
+                )))
+            }
+            MutableStructField::MutableStruct { name, .. } => {
+                let value = self.get_snapshot_value_ref(quote!(#snapshot_name));
+                Some(quote!(#traits::MutableStruct::update_ref(&self.#name, #value)))
+            }
+            MutableStructField::Skipped { .. } => None,
         }
+    }
 
-        impl futures_signals_structs_traits::MutableStruct for #mutable_name {
-            type SnapshotType = #original_ident;
+    /// Returns code that sets this field to the current value of the same field on `other`,
+    /// another instance of the mutable struct. Vectors of structs are copied through a
+    /// snapshot, since their elements can't be matched up otherwise.
+    pub fn get_merger(&self) -> Option<proc_macro2::TokenStream> {
+        let traits = traits_crate();
+        match self {
+            MutableStructField::Basic { name, .. } => {
+                Some(quote!(self.#name.set(other.#name.get_cloned())))
+            }
+            MutableStructField::MutableStruct {
+                name,
+                element_type: Some(_),
+                ..
+            } => {
+                let nested_trait = self.get_nested_trait();
+                Some(quote!(#nested_trait::update(
+                    &self.#name,
+                    #nested_trait::snapshot(&other.#name),
+                )))
+            }
+            MutableStructField::MutableStruct { name, .. } => {
+                Some(quote!(#traits::MutableStruct::merge(&self.#name, &other.#name)))
+            }
+            MutableStructField::Skipped { .. } => None,
+        }
+    }
 
-            fn snapshot(&self) -> #original_ident {
-                #original_ident {
-                    #(#snapshot_fields),*
-                }
+    /// Returns code that replaces the mutable value with the one from a non-mutable version of
+    /// this struct and evaluates to the previous value. Skipped fields keep their value, which
+    /// is also what gets returned for them.
+    pub fn get_replacer(&self, snapshot_name: Ident) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::Basic {
+                name,
+                source_name,
+                wrapper: Some(_),
+                ..
+            } => {
+                let value = self.wrap_value(quote!(#snapshot_name.#source_name));
+                quote!(::std::clone::Clone::clone(&*self.#name.replace(#value)))
+            }
+            MutableStructField::Basic {
+                name,
+                source_name,
+                snapshot_with: Some(snapshot_with),
+                ..
+            } => {
+                let value = self.wrap_value(quote!(#snapshot_name.#source_name));
+                quote!(#snapshot_with(&self.#name.replace(#value)))
             }
+            MutableStructField::Basic {
+                name,
+                source_name,
+                store_as: Some(_),
+                ..
+            } => {
+                let value = self.wrap_value(quote!(#snapshot_name.#source_name));
+                quote!(::std::convert::Into::into(self.#name.replace(#value)))
+            }
+            MutableStructField::Basic {
+                name, source_name, ..
+            } => {
+                let value = self.wrap_value(quote!(#snapshot_name.#source_name));
+                quote!(self.#name.replace(#value))
+            }
+            MutableStructField::MutableStruct { name, .. } => {
+                let nested_trait = self.get_nested_trait();
+                let value = self.get_snapshot_value(quote!(#snapshot_name));
+                quote!(#nested_trait::replace_with(&self.#name, #value))
+            }
+            MutableStructField::Skipped { name, .. } => quote!(self.#name.clone()),
+        }
+    }
 
-            fn update(&self, new_snapshot: #original_ident) {
-                #(#update_fields);*;
+    /// Returns code that updates the mutable value from a non-mutable version of this struct,
+    /// leaving it untouched if it already holds an equal value. Nested structs recurse into
+    /// their own `update_changed`.
+    pub fn get_changed_setter(&self, snapshot_name: Ident) -> Option<proc_macro2::TokenStream> {
+        let traits = traits_crate();
+        match self {
+            MutableStructField::Basic {
+                name,
+                source_name,
+                eq_fn: Some(eq_fn),
+                ..
+            } => {
+                let current = self.get_current_value_ref();
+                let value = self.wrap_value(quote!(value));
+                Some(quote! {{
+                    let value = #snapshot_name.#source_name;
+                    let changed = !#eq_fn(#current, &value);
+                    if changed {
+                        self.#name.set(#value);
+                    }
+                }})
+            }
+            MutableStructField::Basic {
+                name, source_name, ..
+            } => {
+                let value = self.wrap_value(quote!(#snapshot_name.#source_name));
+                Some(quote!(self.#name.set_neq(#value)))
+            }
+            MutableStructField::MutableStruct {
+                name,
+                source_name,
+                element_type: Some(_),
+                ..
+            } => Some(quote!(
+                #traits::vec::MutableStructElements::update_changed(
+                    &self.#name,
+                    #snapshot_name.#source_name,
+                )
+            )),
+            MutableStructField::MutableStruct { name, .. } => {
+                let value = self.get_snapshot_value(quote!(#snapshot_name));
+                Some(quote!(#traits::PartialUpdate::update_changed(&self.#name, #value)))
             }
+            MutableStructField::Skipped { .. } => None,
         }
+    }
 
-        impl Clone for #mutable_name {
-            fn clone(&self) -> #mutable_name {
-                self.snapshot().as_mutable_struct()
+    /// Returns the bound this field needs for `update_changed`, if it takes part in updates
+    /// and is compared with `PartialEq`.
+    pub fn get_changed_bound(&self) -> Option<proc_macro2::TokenStream> {
+        let traits = traits_crate();
+        if self.is_recursive() {
+            return None;
+        }
+        match self {
+            MutableStructField::Basic { eq_fn: Some(_), .. } => None,
+            MutableStructField::Basic { .. } => {
+                let value_type = self.get_value_type();
+                Some(quote!(#value_type: ::std::cmp::PartialEq))
+            }
+            MutableStructField::MutableStruct {
+                element_type: Some(element_type),
+                ..
+            } => Some(quote!(#element_type: #traits::PartialUpdate)),
+            MutableStructField::MutableStruct { .. } => {
+                let mutable_type = self.get_mutable_type();
+                Some(quote!(#mutable_type: #traits::PartialUpdate))
             }
+            MutableStructField::Skipped { .. } => None,
         }
     }
-}
+
+    /// Returns code that borrows the current value of this basic field, looking through its
+    /// `signal_wrap` pointer. The borrow holds the field's lock until the end of the
+    /// statement.
+    fn get_current_value_ref(&self) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::Basic {
+                name,
+                wrapper: Some(_),
+                ..
+            } => quote!(&**self.#name.lock_ref()),
+            _ => {
+                let name = self.get_name();
+                quote!(&*self.#name.lock_ref())
+            }
+        }
+    }
+
+    /// Returns the name of the variant that refers to this field in the generated field
+    /// enum, which is the field name in CamelCase, or `Field<N>` for tuple fields.
+    pub fn get_variant_name(&self) -> Ident {
+        match self.get_name() {
+            Member::Named(ident) => {
+                let name = ident.to_string();
+                let camel_case = name
+                    .trim_start_matches("r#")
+                    .split('_')
+                    .filter(|part| !part.is_empty())
+                    .map(|part| {
+                        let mut chars = part.chars();
+                        chars
+                            .next()
+                            .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                            .unwrap_or_default()
+                    })
+                    .collect::<String>();
+                format_ident!("{}", camel_case)
+            }
+            Member::Unnamed(index) => format_ident!("Field{}", index.index),
+        }
+    }
+
+    /// Returns the variant of the generated field enum for this field. Nested structs carry
+    /// the changes inside them. Skipped fields are never reported as changed.
+    pub fn get_diff_variant(&self) -> Option<proc_macro2::TokenStream> {
+        let traits = traits_crate();
+        let variant_name = self.get_variant_name();
+        match self {
+            MutableStructField::Basic { .. } => Some(quote!(#variant_name)),
+            MutableStructField::MutableStruct {
+                element_type: Some(_),
+                ..
+            } => Some(quote!(#variant_name(::std::vec::Vec<usize>))),
+            MutableStructField::MutableStruct { .. } => {
+                let mutable_type = self.get_mutable_type();
+                Some(quote!(
+                    #variant_name(::std::vec::Vec<<#mutable_type as #traits::Diff>::Field>)
+                ))
+            }
+            MutableStructField::Skipped { .. } => None,
+        }
+    }
+
+    /// Returns code that pushes this field's variant onto `changes` if it differs from
+    /// `other`.
+    pub fn get_diff_check(&self, field_enum: &Ident) -> Option<proc_macro2::TokenStream> {
+        let traits = traits_crate();
+        let name = self.get_name();
+        let source_name = self.get_source_name();
+        let variant_name = self.get_variant_name();
+        match self {
+            MutableStructField::Basic { eq_fn, .. } => {
+                let current = self.get_current_value_ref();
+                let other_value = match self {
+                    MutableStructField::Basic {
+                        store_as: Some(_), ..
+                    } => {
+                        let value = self
+                            .wrap_value(quote!(::std::clone::Clone::clone(&other.#source_name)));
+                        quote!(&#value)
+                    }
+                    _ => quote!(&other.#source_name),
+                };
+                let changed = match eq_fn {
+                    Some(eq_fn) => quote!(!#eq_fn(#current, #other_value)),
+                    None => quote!(#current != #other_value),
+                };
+                Some(quote! {
+                    if #changed {
+                        changes.push(#field_enum::#variant_name);
+                    }
+                })
+            }
+            MutableStructField::MutableStruct { element_type, .. } => {
+                let diff_trait = match element_type {
+                    Some(_) => quote!(#traits::vec::MutableStructElements),
+                    None => quote!(#traits::Diff),
+                };
+                let other_value = self.get_snapshot_value_ref(quote!(other));
+                Some(quote! {
+                    let nested_changes = #diff_trait::diff(&self.#name, #other_value);
+                    if !nested_changes.is_empty() {
+                        changes.push(#field_enum::#variant_name(nested_changes));
+                    }
+                })
+            }
+            MutableStructField::Skipped { .. } => None,
+        }
+    }
+
+    /// Returns the bound this field needs for `diff`, if it is compared at all.
+    pub fn get_diff_bound(&self) -> Option<proc_macro2::TokenStream> {
+        let traits = traits_crate();
+        if self.is_recursive() {
+            return None;
+        }
+        match self {
+            MutableStructField::Basic { eq_fn: Some(_), .. } => None,
+            MutableStructField::Basic {
+                store_as: Some(store_as),
+                ..
+            } => Some(quote!(#store_as: ::std::cmp::PartialEq)),
+            MutableStructField::Basic { ty, .. } => Some(quote!(#ty: ::std::cmp::PartialEq)),
+            MutableStructField::MutableStruct {
+                element_type: Some(element_type),
+                ..
+            } => Some(quote!(#element_type: #traits::Diff)),
+            MutableStructField::MutableStruct { .. } => {
+                let mutable_type = self.get_mutable_type();
+                Some(quote!(#mutable_type: #traits::Diff))
+            }
+            MutableStructField::Skipped { .. } => None,
+        }
+    }
+
+    /// Returns methods that give access to this field's signals. Basic fields get a
+    /// `<name>_signal` accessor for `Copy` values, and `<name>_signal_cloned`,
+    /// `<name>_signal_ref` and `<name>_signal_throttled` accessors for any value. Named basic
+    /// fields also get `with_<name>` and `wait_for_<name>` methods, a `<name>` getter and a
+    /// `set_<name>` setter. Nested structs get a `<name>` accessor returning the nested
+    /// mutable struct. `Vec` fields get a `<name>_signal_vec` accessor. The `_signal` part
+    /// can be renamed per struct or per field. Skipped fields and the positional fields of
+    /// tuple structs get no accessors, unless a tuple field is given an accessor name.
+    pub fn get_accessors(
+        &self,
+        version: Option<&proc_macro2::TokenStream>,
+    ) -> proc_macro2::TokenStream {
+        let struct_accessor = match self {
+            MutableStructField::MutableStruct {
+                vis,
+                name: Member::Named(ident),
+                ..
+            } => {
+                let mutable_type = self.get_mutable_type();
+                quote! {
+                    #vis fn #ident(&self) -> &#mutable_type {
+                        &self.#ident
+                    }
+                }
+            }
+            MutableStructField::Basic {
+                vis,
+                ty,
+                name: Member::Named(ident),
+                ..
+            } => {
+                let setter_name = format_ident!("set_{}", ident);
+                let version_bump = version.map(|version| quote!(#version.bump();));
+                let current = self.unwrap_value(quote!(&*self.#ident.lock_ref()));
+                let value = self.wrap_value(quote!(value));
+                quote! {
+                    #vis fn #ident(&self) -> #ty {
+                        #current
+                    }
+
+                    #vis fn #setter_name(&self, value: #ty) {
+                        self.#ident.set(#value);
+                        #version_bump
+                    }
+                }
+            }
+            _ => quote!(),
+        };
+        let signal_accessors = self.get_signal_accessors(quote!(self), version);
+        quote! {
+            #struct_accessor
+            #signal_accessors
+        }
+    }
+
+    /// Returns the accessors of this field that only produce signals, reading the field
+    /// from `receiver`, which evaluates to the mutable struct. The signals are held back
+    /// during batches if the struct has a `version`.
+    pub fn get_signal_accessors(
+        &self,
+        receiver: proc_macro2::TokenStream,
+        version: Option<&proc_macro2::TokenStream>,
+    ) -> proc_macro2::TokenStream {
+        let signals = signals_crate();
+        let name = self.get_name();
+        match self {
+            MutableStructField::Basic {
+                vis,
+                signal_accessor: Some(signal_accessor),
+                ..
+            } => {
+                let ty = self.get_value_type();
+                let signal_cloned_name = format_ident!("{}_cloned", signal_accessor);
+                let signal_ref_name = format_ident!("{}_ref", signal_accessor);
+                let signal_throttled_name = format_ident!("{}_throttled", signal_accessor);
+                let signal = hold_signal(version, quote!(#receiver.#name.signal()));
+                let signal_cloned = hold_signal(version, quote!(#receiver.#name.signal_cloned()));
+                let signal_ref = hold_signal(version, quote!(#receiver.#name.signal_ref(f)));
+                let wait_for = match name {
+                    Member::Named(ident) => {
+                        let with_name = format_ident!("with_{}", ident);
+                        let wait_for_name = format_ident!("wait_for_{}", ident);
+                        quote! {
+                            #vis fn #with_name<R>(&self, f: impl FnOnce(&#ty) -> R) -> R {
+                                f(&*#receiver.#name.lock_ref())
+                            }
+
+                            #vis fn #wait_for_name(
+                                &self,
+                                predicate: impl Fn(&#ty) -> bool,
+                            ) -> impl ::std::future::Future<Output = ::std::option::Option<#ty>> {
+                                let mut signal = ::std::boxed::Box::pin(#signal_cloned);
+                                ::std::future::poll_fn(move |cx| loop {
+                                    match #signals::signal::Signal::poll_change(signal.as_mut(), cx) {
+                                        ::std::task::Poll::Ready(::std::option::Option::Some(value)) => {
+                                            if predicate(&value) {
+                                                return ::std::task::Poll::Ready(::std::option::Option::Some(value));
+                                            }
+                                        }
+                                        // The signal ends once the field is dropped, after which
+                                        // it can never match.
+                                        ::std::task::Poll::Ready(::std::option::Option::None) => {
+                                            return ::std::task::Poll::Ready(::std::option::Option::None);
+                                        }
+                                        ::std::task::Poll::Pending => return ::std::task::Poll::Pending,
+                                    }
+                                })
+                            }
+                        }
+                    }
+                    Member::Unnamed(_) => quote!(),
+                };
+                quote! {
+                    #vis fn #signal_accessor(&self) -> impl #signals::signal::Signal<Item = #ty>
+                    where
+                        for<'__field> #ty: Copy,
+                    {
+                        #signal
+                    }
+
+                    #vis fn #signal_cloned_name(&self) -> impl #signals::signal::Signal<Item = #ty> {
+                        #signal_cloned
+                    }
+
+                    #vis fn #signal_throttled_name<F, Fut>(
+                        &self,
+                        sleep: F,
+                    ) -> impl #signals::signal::Signal<Item = #ty>
+                    where
+                        F: FnMut() -> Fut,
+                        Fut: ::std::future::Future<Output = ()>,
+                    {
+                        #signals::signal::SignalExt::throttle(#signal_cloned, sleep)
+                    }
+
+                    #vis fn #signal_ref_name<U>(
+                        &self,
+                        f: impl FnMut(&#ty) -> U,
+                    ) -> impl #signals::signal::Signal<Item = U> {
+                        #signal_ref
+                    }
+
+                    #wait_for
+                }
+            }
+            MutableStructField::MutableStruct {
+                vis,
+                signal_accessor: Some(signal_accessor),
+                ..
+            } => match self.get_vec_element_type() {
+                Some(element_type) => {
+                    let signal_vec_name = format_ident!("{}_vec", signal_accessor);
+                    quote! {
+                        #vis fn #signal_vec_name(
+                            &self,
+                        ) -> impl #signals::signal_vec::SignalVec<Item = #element_type> {
+                            #receiver.#name.signal_vec_cloned()
+                        }
+                    }
+                }
+                None => quote!(),
+            },
+            _ => quote!(),
+        }
+    }
+
+    /// Returns the element type of a `Vec` field that is stored as a `MutableVec`.
+    fn get_vec_element_type(&self) -> Option<&Type> {
+        match self {
+            MutableStructField::MutableStruct {
+                ty: Type::Path(type_path),
+                mutable_type: None,
+                ..
+            } => {
+                let last_component = type_path.path.segments.last()?;
+                if last_component.ident != "Vec" {
+                    return None;
+                }
+                MutableStructField::first_type_argument(last_component)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns code that creates a signal of this field's snapshot, or None for skipped
+    /// fields, which never change.
+    pub fn get_snapshot_signal(&self) -> Option<proc_macro2::TokenStream> {
+        match self {
+            MutableStructField::Basic { name, .. } if self.is_converted() => {
+                let snapshot = self.unwrap_value(quote!(value));
+                Some(quote!(self.#name.signal_ref(|value| #snapshot)))
+            }
+            MutableStructField::Basic { name, .. } => Some(quote!(self.#name.signal_cloned())),
+            MutableStructField::MutableStruct { name, .. } => {
+                let nested_trait = self.get_nested_trait();
+                Some(quote!(#nested_trait::snapshot_signal(&self.#name)))
+            }
+            MutableStructField::Skipped { .. } => None,
+        }
+    }
+
+    /// Returns an identifier that can hold this field's value in generated code.
+    pub fn get_binding(&self) -> Ident {
+        match self.get_name() {
+            Member::Named(ident) => ident.clone(),
+            Member::Unnamed(index) => format_ident!("field_{}", index.index),
+        }
+    }
+
+    /// Returns the fields of the original struct that this nested field is gathered from with
+    /// `#[signal_snapshot_flatten]`, or None if it has a field of its own.
+    fn get_flattened(&self) -> Option<&[Member]> {
+        match self {
+            MutableStructField::MutableStruct { flattened, .. } if !flattened.is_empty() => {
+                Some(flattened)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns code that moves this field's value out of `snapshot`, a non-mutable version of
+    /// the struct. Flattened fields are gathered into a value of the nested struct.
+    pub fn get_snapshot_value(
+        &self,
+        snapshot: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        match self.get_flattened() {
+            Some(members) => {
+                let ty = type_as_expr_path(self.get_type());
+                quote!(#ty { #(#members: #snapshot.#members),* })
+            }
+            None => {
+                let source_name = self.get_source_name();
+                quote!(#snapshot.#source_name)
+            }
+        }
+    }
+
+    /// Returns code that borrows this field's value on `snapshot`, like `get_snapshot_value`.
+    /// Flattened fields are cloned into a temporary value of the nested struct.
+    pub fn get_snapshot_value_ref(
+        &self,
+        snapshot: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        match self.get_flattened() {
+            Some(members) => {
+                let ty = type_as_expr_path(self.get_type());
+                quote!(&#ty { #(#members: ::std::clone::Clone::clone(&#snapshot.#members)),* })
+            }
+            None => {
+                let source_name = self.get_source_name();
+                quote!(&#snapshot.#source_name)
+            }
+        }
+    }
+
+    /// Returns the field initializers that store `value` in a non-mutable version of the
+    /// struct. Flattened fields are spread out of `value`, which has to be a variable.
+    pub fn get_snapshot_initializers(
+        &self,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        match self.get_flattened() {
+            Some(members) => quote!(#(#members: #value.#members),*),
+            None => {
+                let source_name = self.get_source_name();
+                quote!(#source_name: #value)
+            }
+        }
+    }
+
+    /// Returns the doc comments of this field.
+    pub fn get_docs(&self) -> &[Attribute] {
+        match self {
+            MutableStructField::Basic { docs, .. } => docs,
+            MutableStructField::MutableStruct { docs, .. } => docs,
+            MutableStructField::Skipped { docs, .. } => docs,
+        }
+    }
+
+    /// Returns the visibility of this field.
+    pub fn get_vis(&self) -> &Visibility {
+        match self {
+            MutableStructField::Basic { vis, .. } => vis,
+            MutableStructField::MutableStruct { vis, .. } => vis,
+            MutableStructField::Skipped { vis, .. } => vis,
+        }
+    }
+
+    /// Marks this field as recursive if it is a nested struct whose type mentions any of
+    /// `own_types`, which are the names of the original and mutable structs.
+    pub fn mark_if_recursive(&mut self, own_types: &[Ident]) {
+        let field_tokens = match self {
+            MutableStructField::MutableStruct {
+                ty, mutable_type, ..
+            } => quote!(#ty #mutable_type),
+            _ => return,
+        };
+        if let MutableStructField::MutableStruct { recursive, .. } = self {
+            *recursive = tokens_mention(field_tokens, own_types);
+        }
+    }
+
+    /// Returns true for nested fields whose type refers back to the struct itself. Optional
+    /// impls can't require the mutable type of such fields to implement a trait, since
+    /// checking that would require the impl itself.
+    fn is_recursive(&self) -> bool {
+        matches!(
+            self,
+            MutableStructField::MutableStruct {
+                recursive: true,
+                ..
+            }
+        )
+    }
+
+    /// Returns the type that optional impls require to implement their trait for this field.
+    /// That is the type of its current value, except for recursive fields, which are
+    /// bounded by their type on the original struct instead.
+    pub fn get_bounded_value_type(&self) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::MutableStruct {
+                ty,
+                recursive: true,
+                ..
+            } => quote!(#ty),
+            _ => self.get_value_type(),
+        }
+    }
+
+    /// Returns code that borrows a value of this field on `receiver` that can be compared or
+    /// hashed. Recursive fields are compared through a snapshot, since their bound is on the
+    /// snapshot type.
+    pub fn get_comparable_value_ref(
+        &self,
+        receiver: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::MutableStruct {
+                name,
+                recursive: true,
+                ..
+            } => {
+                let nested_trait = self.get_nested_trait();
+                quote!(&#nested_trait::snapshot(&#receiver.#name))
+            }
+            _ => self.get_value_ref(receiver),
+        }
+    }
+
+    /// Changes the visibility of this field on the mutable struct, and of its accessors.
+    pub fn set_vis(&mut self, new_vis: Visibility) {
+        match self {
+            MutableStructField::Basic { vis, .. } => *vis = new_vis,
+            MutableStructField::MutableStruct { vis, .. } => *vis = new_vis,
+            MutableStructField::Skipped { vis, .. } => *vis = new_vis,
+        }
+    }
+
+    /// Wraps a value of the original field type in this field's `signal_wrap` pointer, or
+    /// converts it to its `signal_store_as` type, if it has one. Values are clamped to the
+    /// field's `signal_min` and `signal_max` first.
+    fn wrap_value(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let value = self.clamp_value(value);
+        match self {
+            MutableStructField::Basic {
+                wrapper: Some(wrapper),
+                ..
+            } => quote!(#wrapper::new(#value)),
+            MutableStructField::Basic {
+                update_with: Some(update_with),
+                ..
+            } => quote!(#update_with(#value)),
+            MutableStructField::Basic {
+                store_as: Some(store_as),
+                ..
+            } => quote!(::std::convert::Into::<#store_as>::into(#value)),
+            _ => value,
+        }
+    }
+
+    /// Clamps a value of the original field type to this field's `signal_min` and
+    /// `signal_max`, if it has either.
+    fn clamp_value(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::Basic { min, max, .. } if min.is_some() || max.is_some() => {
+                let traits = traits_crate();
+                let limit = |limit: &Option<Box<syn::Expr>>| match limit {
+                    Some(limit) => quote!(::std::option::Option::Some(#limit)),
+                    None => quote!(::std::option::Option::None),
+                };
+                let (min, max) = (limit(min), limit(max));
+                quote!(#traits::clamp_to_limits(#value, #min, #max))
+            }
+            _ => value,
+        }
+    }
+
+    /// Returns true for basic fields whose stored value is not the original field type,
+    /// because of `signal_wrap` or `signal_store_as`.
+    fn is_converted(&self) -> bool {
+        matches!(
+            self,
+            MutableStructField::Basic {
+                wrapper: Some(_),
+                ..
+            } | MutableStructField::Basic {
+                store_as: Some(_),
+                ..
+            }
+        )
+    }
+
+    /// Turns `stored`, a reference to the value stored in this basic field's Mutable, back
+    /// into an owned value of the original field type.
+    fn unwrap_value(&self, stored: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            MutableStructField::Basic {
+                wrapper: Some(_), ..
+            } => quote!(::std::clone::Clone::clone(&**#stored)),
+            MutableStructField::Basic {
+                snapshot_with: Some(snapshot_with),
+                ..
+            } => quote!(#snapshot_with(#stored)),
+            MutableStructField::Basic {
+                store_as: Some(_), ..
+            } => quote!(::std::convert::Into::into(::std::clone::Clone::clone(#stored))),
+            _ => quote!(::std::clone::Clone::clone(#stored)),
+        }
+    }
+
+    /// Returns the bounds this field's type needs when it mentions a type parameter of a
+    /// generic struct.
+    pub fn get_generic_bounds(&self) -> Vec<WherePredicate> {
+        let traits = traits_crate();
+        match self {
+            MutableStructField::Basic { ty, .. } | MutableStructField::Skipped { ty, .. } => {
+                vec![syn::parse_quote!(#ty: ::std::clone::Clone + 'static)]
+            }
+            MutableStructField::MutableStruct {
+                ty,
+                mutable_type: Some(mutable_type),
+                element_type: Some(element_type),
+                ..
+            } => vec![
+                syn::parse_quote!(
+                    #mutable_type: #traits::vec::MutableStructElements<
+                        Element = #element_type,
+                    >
+                ),
+                syn::parse_quote!(
+                    #ty: ::std::iter::IntoIterator<
+                        Item = <#element_type as #traits::MutableStruct>::SnapshotType,
+                    >
+                ),
+                syn::parse_quote!(#ty: ::std::clone::Clone),
+            ],
+            MutableStructField::MutableStruct {
+                ty,
+                mutable_type: Some(mutable_type),
+                ..
+            } => vec![
+                syn::parse_quote!(
+                    #mutable_type: #traits::MutableStruct<SnapshotType = #ty>
+                ),
+                syn::parse_quote!(#ty: ::std::clone::Clone),
+            ],
+            MutableStructField::MutableStruct { ty, .. } => vec![syn::parse_quote!(
+                #ty: #traits::AsMutableStruct + ::std::clone::Clone
+            )],
+        }
+    }
+
+    /// Returns the type of this field on the original struct.
+    pub fn get_type(&self) -> &Type {
+        match self {
+            MutableStructField::Basic { ty, .. } => ty,
+            MutableStructField::MutableStruct { ty, .. } => ty,
+            MutableStructField::Skipped { ty, .. } => ty,
+        }
+    }
+
+    /// Returns the name of this field on the mutable struct, which is its index for tuple
+    /// structs.
+    pub fn get_name(&self) -> &Member {
+        match self {
+            MutableStructField::Basic { name, .. } => name,
+            MutableStructField::MutableStruct { name, .. } => name,
+            MutableStructField::Skipped { name, .. } => name,
+        }
+    }
+
+    /// Returns the name of this field on the original struct, which differs from its name
+    /// on the mutable struct if it was renamed with `#[signal_rename]`.
+    pub fn get_source_name(&self) -> &Member {
+        match self {
+            MutableStructField::Basic { source_name, .. } => source_name,
+            MutableStructField::MutableStruct { source_name, .. } => source_name,
+            MutableStructField::Skipped { source_name, .. } => source_name,
+        }
+    }
+
+    /// Returns true if the field should be stored as a plain `Mutable` rather than a nested
+    /// MutableStruct.
+    fn field_is_primitive(input: &Field) -> bool {
+        MutableStructField::type_is_primitive(&input.ty)
+    }
+
+    /// Only the last segment of a type's path is looked at, since the derive can't resolve
+    /// paths or aliases. Rust's primitive types and anything listed in `KNOWN_BASIC_TYPES`
+    /// are considered primitive, and any other name is assumed to be a struct, including
+    /// lowercase aliases. An `Option`, `Box`, `Arc` or `Rc` is primitive when the type it
+    /// wraps is. Arrays, tuples and trait objects are always primitive, and are stored and
+    /// replaced as a whole.
+    fn type_is_primitive(ty: &Type) -> bool {
+        if let Type::Array(_) | Type::Tuple(_) | Type::TraitObject(_) = ty {
+            true
+        } else if let Type::Paren(paren) = ty {
+            MutableStructField::type_is_primitive(&paren.elem)
+        } else if let Type::Path(type_path) = ty {
+            let last_component = type_path.path.segments.last().unwrap();
+            let name = last_component.ident.to_string();
+            if ["Option", "Box", "Arc", "Rc"].contains(&name.as_str()) {
+                return MutableStructField::first_type_argument(last_component)
+                    .map(MutableStructField::type_is_primitive)
+                    .unwrap_or(false);
+            }
+            PRIMITIVE_TYPES.contains(&name.as_str()) || KNOWN_BASIC_TYPES.contains(&name.as_str())
+        } else {
+            false
+        }
+    }
+
+    /// Returns the first generic type argument of a path segment, like `T` in `Option<T>`.
+    fn first_type_argument(segment: &syn::PathSegment) -> Option<&Type> {
+        if let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments {
+            arguments.args.iter().find_map(|argument| match argument {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Derives a function called `as_mutable_struct()` that returns a version of the struct
+/// where all fields are Mutable objects.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct PlayerScore {
+///         hits: u32,
+///         multiplier: f32,
+///     }
+///
+///     fn main() {
+///         let score = PlayerScore {
+///             hits: 4,
+///             multiplier: 0.4,
+///         };
+///         let mutable_score = score.as_mutable_struct();
+///         mutable_score.hits.set(6);
+///         mutable_score.multiplier.set(0.5);
+///     }
+/// ```
+/// By default this creates a new struct called MutablePlayerScore that can also be
+/// constructed directly as necessary. Doc comments on the struct and its fields are copied
+/// onto it.
+/// ```ignore
+///     let mutable_score = MutablePlayerScore {
+///         hits: Mutable::new(5),
+///         multiplier: Mutable::new(1.4),
+///     };
+/// ```
+/// `new` does the same from the raw value of every field, in declaration order. Nested
+/// structs are converted with `as_mutable_struct`.
+/// ```ignore
+///     let mutable_score = MutablePlayerScore::new(5, 1.4);
+/// ```
+/// A `#[non_exhaustive]` struct makes its mutable struct non-exhaustive too, and gets no
+/// `new`. Other crates build it from a snapshot or with the builder instead.
+///
+/// For larger structs a builder is generated as well. Any field that is not set is given
+/// its `Default` value.
+/// ```ignore
+///     let mutable_score = MutablePlayerScoreBuilder::new()
+///         .hits(5)
+///         .build();
+/// ```
+/// The conversions are also available through `From` and `Into`.
+/// ```ignore
+///     let mutable_score = MutablePlayerScore::from(score);
+///     let score: PlayerScore = (&mutable_score).into();
+/// ```
+/// Either way you construct it, the mutable object can be 'snapshotted' into the
+/// original struct.
+/// ```ignore
+///     assert_eq!(mutable_score.snapshot(), PlayerScore {
+///         hits: 5,
+///         multiplier: 1.4,
+///     });
+/// ```
+/// `snapshot_into` writes a snapshot into an existing struct instead, reusing the buffers
+/// of its strings and vectors, which is useful for reading the same struct every frame.
+/// ```ignore
+///     let mut score = mutable_score.snapshot();
+///     loop {
+///         mutable_score.snapshot_into(&mut score);
+///     }
+/// ```
+/// The mutable value can also be updated to match a new static struct.
+/// ```ignore
+///     mutable_score.update(PlayerScore {
+///         hits: 50,
+///         multiplier: 15,
+///     });
+///     assert_eq!(mutable_score.snapshot(), PlayerScore {
+///         hits: 50,
+///         multiplier: 15,
+///     });
+/// ```
+/// Mutable structs can be compared by their current values with `==`. Adding
+/// `#[signal_hash]` also implements `Eq` and `Hash` over the current values, which is only
+/// possible when no field is a float. Like any key with interior mutability, a mutable
+/// struct must not be changed while it is stored in a `HashSet` or `HashMap`.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[signal_hash]
+///     struct Coordinate {
+///         x: i32,
+///         y: i32,
+///     }
+/// ```
+/// `diff` lists the fields whose current value differs from a snapshot, using a generated
+/// `PlayerScoreFieldDiff` enum. Nested structs report the changes inside them.
+/// ```ignore
+///     assert_eq!(mutable_score.diff(&other_score), vec![PlayerScoreFieldDiff::Hits]);
+/// ```
+/// `set_field` sets any field named by the generated `PlayerScoreField` enum, for building
+/// generic editors. Values are passed in a `PlayerScoreFieldValue` enum with a variant for
+/// each field, and are handed back if they belong to a different field.
+/// ```ignore
+///     mutable_score.set_field(PlayerScoreField::Hits, PlayerScoreFieldValue::Hits(3))?;
+/// ```
+/// `apply_diff` sets several fields at once from a generated `ApplyablePlayerScore` patch,
+/// which holds an `Option` for each field. Fields left as `None` are not touched, and
+/// nested structs are patched with their own patch type.
+/// ```ignore
+///     mutable_score.apply_diff(ApplyablePlayerScore {
+///         hits: Some(8),
+///         ..Default::default()
+///     });
+/// ```
+/// Each stored field also gets a marker type in a generated module, such as
+/// `player_score_fields::Hits`, which selects the field in `get_field_signal`. This lets
+/// generic code, such as the output of other macros, subscribe to any field of any mutable
+/// struct.
+/// ```ignore
+///     fn bind<M: GetFieldSignal<F>, F>(mutable: &M, field: F) {
+///         spawn_local(mutable.get_field_signal(field).for_each(|value| { ... }));
+///     }
+///
+///     bind(&mutable_score, player_score_fields::Hits);
+/// ```
+/// `snapshot` and `update` lock one field at a time, so a snapshot taken while another
+/// thread updates the struct may mix old and new values. `consistent_snapshot` and
+/// `consistent_update` hold the locks of every field at once to rule this out.
+/// ```ignore
+///     let score = mutable_score.consistent_snapshot();
+/// ```
+/// `read` takes the same locks without building a snapshot, and returns a
+/// `<Name>ReadGuard` through which the fields can be read in place until it is dropped.
+/// ```ignore
+///     let score = mutable_score.read();
+///     let total = *score.hits as f32 * *score.multiplier;
+/// ```
+/// A struct can name a function that validates snapshots with `#[signal_validate]`.
+/// `try_update` calls it before changing any field, and leaves the struct untouched if it
+/// returns a `ValidationError`. `update` does not validate snapshots.
+/// ```ignore
+///     fn validate(score: &PlayerScore) -> Result<(), ValidationError> {
+///         if score.multiplier < 0.0 {
+///             return Err(ValidationError::new("multiplier must not be negative"));
+///         }
+///         Ok(())
+///     }
+///
+///     #[derive(AsMutableStruct)]
+///     #[signal_validate = "validate"]
+///     struct PlayerScore {
+///         hits: u32,
+///         multiplier: f32,
+///     }
+/// ```
+/// `replace_with` works like `update`, but also returns the snapshot it replaced.
+/// ```ignore
+///     let previous = mutable_score.replace_with(PlayerScore {
+///         hits: 0,
+///         multiplier: 1,
+///     });
+/// ```
+/// `field_names` and `field_debug_values` list the fields and their current values, for
+/// building generic inspectors.
+/// ```ignore
+///     for (name, value) in mutable_score.field_debug_values() {
+///         println!("{}: {}", name, value);
+///     }
+/// ```
+/// `merge` copies the current values of another mutable struct, field by field.
+/// ```ignore
+///     mutable_score.merge(&other_mutable_score);
+/// ```
+/// `update_ref` takes the snapshot by reference instead, and clones just the values it
+/// stores.
+/// ```ignore
+///     mutable_score.update_ref(&saved_score);
+/// ```
+/// When the fields implement `PartialEq`, `update_changed` only sets the fields whose
+/// value actually differs, so subscribers of unchanged fields are not notified.
+/// ```ignore
+///     mutable_score.update_changed(PlayerScore {
+///         hits: 50,
+///         multiplier: 20,
+///     });
+/// ```
+/// `update` always sets every field, even to an identical value. Because `NaN != NaN`, a
+/// float field holding `NaN` is also set by every `update_changed`. A field can provide its
+/// own comparison, taking two references to the value, with `#[signal_eq_fn]`.
+/// ```ignore
+///     fn same_bits(a: &f32, b: &f32) -> bool {
+///         a.to_bits() == b.to_bits()
+///     }
+///
+///     #[derive(AsMutableStruct)]
+///     struct PlayerScore {
+///         hits: u32,
+///         #[signal_eq_fn = "same_bits"] multiplier: f32,
+///     }
+/// ```
+/// Each field gets accessors for subscribing to its changes, which is the intended way to
+/// observe a mutable struct. `Copy` fields get `<field>_signal()`, any basic field gets
+/// `<field>_signal_cloned()` and nested struct fields get `<field>()`, which returns the
+/// nested mutable struct. `Vec` fields also get `<field>_signal_vec()`. Accessors of raw
+/// identifiers like `r#type` drop the prefix, as in `type_signal()`.
+/// ```ignore
+///     let points_signal = mutable_score.hits_signal();
+///     let nested_score = mutable_game_score.player_1().hits_signal();
+/// ```
+/// Basic fields can also be read and written through methods, without reaching for their
+/// `Mutable`, with `<field>()` and `set_<field>()`.
+/// ```ignore
+///     mutable_score.set_hits(mutable_score.hits() + 1);
+/// ```
+/// `#[signal_min]` and `#[signal_max]` clamp the values that setters, constructors and
+/// updates store in a basic field to a range. Setting the field's `Mutable` directly is not
+/// clamped.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Volume {
+///         #[signal_min = "0"] #[signal_max = "100"] level: u32,
+///     }
+///
+///     mutable_volume.set_level(150);
+///     assert_eq!(mutable_volume.level(), 100);
+/// ```
+/// `wait_for_<field>()` resolves with a basic field's value once it matches a predicate, or
+/// with None if the mutable struct is dropped first.
+/// ```ignore
+///     let hits = mutable_score.wait_for_hits(|hits| *hits >= 100).await;
+/// ```
+/// Large fields can be read without cloning them, either once with `with_<field>()` or on
+/// every change with `<field>_signal_ref()`, which maps a borrowed value.
+/// ```ignore
+///     let page_count = mutable_book.with_text(|text| text.len() / 2000);
+///     let length_signal = mutable_book.text_signal_ref(|text| text.len());
+/// ```
+/// A field can have a different name on the mutable struct with `#[signal_rename]`, which
+/// renames its accessors as well. Snapshots and updates still use the original name.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Item {
+///         #[signal_rename = "kind"] r#type: String,
+///     }
+///
+///     let kind_signal = mutable_item.kind_signal_cloned();
+/// ```
+/// `<field>_signal_throttled()` waits for a future after every value it produces, and then
+/// skips to the latest value, to avoid reacting to every step of a rapidly changing field.
+/// The future comes from a callback, so any timer can be used.
+/// ```ignore
+///     let hits_signal = mutable_score.hits_signal_throttled(|| sleep(Duration::from_millis(100)));
+/// ```
+/// Accessors that would collide with other methods can be renamed, either per field with
+/// `#[signal_accessor]` or for the whole struct by replacing the `_signal` suffix.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[signal_accessor_suffix = "_changes"]
+///     struct PlayerScore {
+///         #[signal_accessor = "observe_hits"] hits: u32,
+///         multiplier: f32,
+///     }
+///
+///     let hits_signal = mutable_score.observe_hits();
+///     let multiplier_signal = mutable_score.multiplier_changes_cloned();
+/// ```
+/// Other derives can be applied to the mutable struct with `#[mutable_derive]`. Forwarding
+/// `Debug`, `Clone`, `PartialEq`, `Eq`, `Hash` or `Default` replaces the impl that would
+/// otherwise be generated for it. Note that a derived `Clone` shares the field values with
+/// the original, since cloning a `Mutable` clones a handle to the same value.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[mutable_derive(Debug)]
+///     struct PlayerScore {
+///         hits: u32,
+///         multiplier: f32,
+///     }
+/// ```
+/// Fields of the mutable struct and their accessors have the same visibility as the fields
+/// of the original struct, unless `#[mutable_vis]` sets one for all of them. Everything is
+/// generated next to the original struct, so restricted visibilities such as `pub(super)`
+/// and `pub(in crate::path)` are copied as written and resolve to the same module. The mutable
+/// struct itself always has the visibility of the original struct, since each names the
+/// other in its trait impls.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[mutable_vis = "pub"]
+///     pub struct Settings {
+///         volume: u8,
+///     }
+/// ```
+/// The name, field visibility and derives of the mutable struct can also be set together in
+/// a single `#[mutable(...)]` attribute.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[mutable(name = "LivePlayerScore", vis = "pub", derive(Debug))]
+///     pub struct PlayerScore {
+///         hits: u32,
+///     }
+/// ```
+/// The whole struct can be observed as well, through a signal that produces a new
+/// snapshot whenever any field changes.
+/// ```ignore
+///     let snapshots = mutable_score.snapshot_signal();
+/// ```
+/// `observe` and `observe_changes` wrap that signal in a future that calls a callback with
+/// every snapshot, ready to be spawned.
+/// ```ignore
+///     spawn_local(mutable_score.observe(|score| println!("{:?}", score)));
+/// ```
+/// Without an executor, `snapshot_poller` can be checked from a synchronous loop instead,
+/// and produces a snapshot whenever the struct changed since the last check.
+/// ```ignore
+///     let mut poller = mutable_score.snapshot_poller();
+///     loop {
+///         if let Some(score) = poller.try_next() {
+///             render(&score);
+///         }
+///     }
+/// ```
+/// `freeze` turns the struct into a `ReadOnlyMutablePlayerScore` handle, which has the same
+/// signal accessors and `snapshot` methods but can't change any field. To keep writing to
+/// the struct, share it in an `Arc` with `into_shared` and convert that into the handle
+/// instead. Clones of the `Arc` all see the same values.
+/// ```ignore
+///     let mutable_score = score.as_mutable_struct().into_shared();
+///     let read_only_score = ReadOnlyMutablePlayerScore::from(mutable_score.clone());
+///     let hits_signal = read_only_score.hits_signal();
+/// ```
+/// Annotating the struct with `#[signal_versioned]` adds a hidden version field to the
+/// mutable struct and implements `Versioned`, so it can no longer be built with a struct
+/// literal. Changes made inside `batch` then reach the snapshot signal, and the signal
+/// accessors of each field, as a single value once the batch ends. Signals polled while it
+/// is running, even on another thread, wait for it to end.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[signal_versioned]
+///     struct PlayerScore {
+///         hits: u32,
+///         multiplier: f32,
+///     }
+///
+///     mutable_score.batch(|score| {
+///         score.hits.set(6);
+///         score.multiplier.set(0.5);
+///     });
+/// ```
+/// `version_signal` counts changes instead, without taking snapshots. Every subscriber sees
+/// the same version, which goes up once per setter call, `update`, `apply_diff` or batch.
+/// Setting a field's `Mutable` directly outside of a batch isn't counted, so only changes
+/// made through the mutable struct's own methods or inside a batch are detected.
+/// ```ignore
+///     let versions = mutable_score.version_signal();
+/// ```
+/// `map_snapshot` projects that signal into a computed view of the struct.
+/// ```ignore
+///     let total = mutable_score.map_snapshot(|score| score.hits as f32 * score.multiplier);
+/// ```
+/// `snapshot_signal_dedupe` skips snapshots equal to the previous one, so setting a field to
+/// its current value produces nothing.
+/// ```ignore
+///     let snapshots = mutable_score.snapshot_signal_dedupe();
+/// ```
+/// `zip_snapshot_signal` pairs the snapshots of two independent mutable structs.
+/// ```ignore
+///     let view = mutable_score.zip_snapshot_signal(&mutable_settings);
+/// ```
+/// `is_dirty_signal` tells whether the struct currently differs from a baseline snapshot.
+/// ```ignore
+///     let unsaved_changes = mutable_score.is_dirty_signal(saved_score.clone());
+/// ```
+/// `Vec` and `BTreeMap` fields become a `MutableVec` and a `MutableBTreeMap` respectively.
+/// To observe the fields of each element in a `Vec` of structs, opt into a
+/// `MutableStructVec` instead.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Team {
+///         #[mutable_type = "MutableStructVec<MutablePlayerScore>"]
+///         players: Vec<PlayerScore>,
+///     }
+/// ```
+/// To keep elements that are identified by a key, such as the ID of an entity, across
+/// updates that reorder them, name the key field with `#[signal_key]` and opt into a
+/// `MutableKeyedVec`. Updates then match elements by key rather than by position.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[signal_key = "id"]
+///     struct Entity {
+///         id: u32,
+///         health: u32,
+///     }
+///
+///     #[derive(AsMutableStruct)]
+///     struct World {
+///         #[mutable_type = "MutableKeyedVec<MutableEntity>"]
+///         entities: Vec<Entity>,
+///     }
+/// ```
+/// Maps of structs work the same way with `MutableStructMap`, which keeps a mutable struct
+/// for each value and matches them to snapshots by key. futures-signals only provides an
+/// ordered mutable map, so the field must be a `BTreeMap` rather than a `HashMap`.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct League {
+///         #[mutable_type = "MutableStructMap<String, MutablePlayerScore>"]
+///         scores: BTreeMap<String, PlayerScore>,
+///     }
+/// ```
+/// A nested struct can be flattened into its parent with `#[signal_flatten]`, which makes
+/// the parent's mutable struct deref to the nested one. Its fields and accessors can then
+/// be used as if they belonged to the parent, while snapshots keep the nested struct. Only
+/// one field per struct can be flattened.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct RankedScore {
+///         rank: u32,
+///         #[signal_flatten] score: PlayerScore,
+///     }
+///
+///     mutable_ranked_score.hits.set(5);
+/// ```
+/// Newtypes can forward to the mutable version of their only field with
+/// `#[signal_transparent]`, so its methods can be called on the mutable struct directly.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[signal_transparent]
+///     struct Meters(f32);
+///
+///     mutable_meters.set(3.0);
+/// ```
+/// Nested structs that are rarely observed can be built on first access instead, by
+/// annotating them with `#[signal_lazy]`. The field becomes a `MutableLazy`, which holds the
+/// snapshot until the nested mutable struct is first reached through `Deref` or `get`.
+/// Snapshots and updates before then use the snapshot directly.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Save {
+///         #[signal_lazy] history: History,
+///     }
+///
+///     let entries = mutable_save.history.entries_signal_vec();
+/// ```
+/// Only the outer level of a nested container is mutable, so the inner vectors of a
+/// `Vec<Vec<T>>` are plain values. A `MutableStructVec<MutableVec<T>>` makes them observable.
+///
+/// An `Option` of a basic type is stored as a single `Mutable<Option<T>>`. An `Option` of a
+/// nested struct becomes a `MutableOption`, which keeps the nested mutable struct alive
+/// across updates that stay `Some`.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Profile {
+///         nickname: Option<String>,
+///         best_score: Option<PlayerScore>,
+///     }
+/// ```
+/// A `Result` is always stored as a single `Mutable<Result<T, E>>`, even if it holds a
+/// struct, and is replaced as a whole rather than updated in place.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Download {
+///         status: Result<PlayerScore, String>,
+///     }
+/// ```
+/// A `Box` of a nested struct becomes a `Box` of the nested mutable struct, while a `Box` of
+/// a basic type is stored in a `Mutable` like the type itself.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Match {
+///         winner: Box<PlayerScore>,
+///     }
+/// ```
+/// Boxes also allow recursive structs such as trees. Opting into a `MutableStructVec` of
+/// boxed mutable nodes makes every node in the tree observable.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Node {
+///         value: i32,
+///         #[mutable_type = "MutableStructVec<Box<MutableNode>>"] children: Vec<Box<Node>>,
+///     }
+///
+///     mutable_tree.children.lock_ref()[0].value.set(5);
+/// ```
+/// `Arc` and `Rc` work the same way. A pointer to a nested struct becomes a pointer to the
+/// nested mutable struct, so it can be shared, and snapshots allocate a new pointer around
+/// a fresh snapshot. A pointer to a basic value is stored in a `Mutable`, and snapshots
+/// share the pointer rather than cloning the value behind it.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Config {
+///         shared: Arc<Settings>,
+///         motd: Arc<String>,
+///     }
+/// ```
+/// Trait objects behind a pointer are basic values. Snapshots clone the pointer, so they need
+/// one that is `Clone`, such as `Arc<dyn Fn()>`. A `Box<dyn Fn()>` can't be cloned, so it
+/// has to be skipped with `#[signal_skip]`, which leaves the struct without snapshots.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Button {
+///         on_click: Arc<dyn Fn() -> i32>,
+///         #[signal_skip] on_press: Box<dyn Fn() -> i32>,
+///     }
+/// ```
+/// Fields gated by `#[cfg]` are removed before the derive runs, so the mutable struct and
+/// all generated methods only include the fields that are enabled.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Telemetry {
+///         frames: u32,
+///         #[cfg(feature = "profiling")] frame_times: Vec<Duration>,
+///     }
+/// ```
+/// The same goes for `#[cfg_attr]`, so attributes like `MutableStructName` can be set
+/// conditionally, or emitted along with the struct by a `macro_rules!` macro. Field types
+/// passed to such a macro as `$ty:ty` are treated like types written out directly.
+/// ```ignore
+///     macro_rules! counter {
+///         ($mutable_name:literal, $ty:ty) => {
+///             #[derive(AsMutableStruct)]
+///             #[MutableStructName = $mutable_name]
+///             struct Counter {
+///                 count: $ty,
+///             }
+///         };
+///     }
+///
+///     counter!("CounterState", Option<u32>);
+/// ```
+/// Tuple structs are supported as well, and produce a mutable tuple struct.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Point(f32, f32);
+///
+///     let mutable_point = Point(1.0, 2.0).as_mutable_struct();
+///     mutable_point.0.set(3.0);
+/// ```
+/// Generic structs produce a generic mutable struct. Fields whose type is a type parameter
+/// are treated as nested structs, and basic values such as `u32` and `String` implement
+/// `AsMutableStruct` as a plain `Mutable`, so either can be used as the parameter.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Wrapper<T> {
+///         value: T,
+///     }
+///
+///     let mutable_count: MutableWrapper<u32> = Wrapper { value: 5 }.as_mutable_struct();
+///     mutable_count.value.set(6);
+/// ```
+/// Const parameters carry over as well, which suits fixed-capacity buffers of arrays.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Buffer<const N: usize> {
+///         data: [u8; N],
+///     }
+///
+///     let mutable_buffer = Buffer { data: [0; 8] }.as_mutable_struct();
+///     mutable_buffer.data.lock_mut()[0] = 7;
+/// ```
+/// The derive can't resolve types, so it guesses which fields are nested structs from the
+/// last segment of their type's path, ignoring any generic arguments. Primitive types like
+/// `u32`, and well-known std types like `String`, `Cow`, `Duration` and `PathBuf`, are
+/// stored in a `Mutable`. Anything else, including a fully-qualified path like
+/// `crate::scoring::PlayerScore` or a lowercase alias, is treated as a nested struct.
+/// Lowercase aliases of primitive types still work this way, as a nested `Mutable`, but
+/// don't get the signal accessors of basic fields.
+///
+/// The `#[mutable_type]` annotation overrides this guess and names the mutable type to
+/// use for the field explicitly.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct GameScore {
+///         #[mutable_type = "MutablePlayerScore"] player_1: PlayerScore,
+///         #[mutable_type = "MutablePlayerScore"] player_2: PlayerScore,
+///     }
+/// ```
+/// `VecDeque` fields become a `MutableVecDeque`, which stores the elements from front to
+/// back in a `MutableVec`.
+/// ```ignore
+///     mutable_queue.jobs.push_front(job);
+/// ```
+/// `HashSet` and `BTreeSet` fields are stored in a single `Mutable` as well, and can be
+/// changed in place through `lock_mut`.
+/// ```ignore
+///     mutable_profile.tags.lock_mut().insert("admin".to_string());
+/// ```
+/// Fixed-size arrays are stored in a single `Mutable`, so setting an element means replacing
+/// the whole array.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Board {
+///         cells: [u8; 9],
+///     }
+///
+///     mutable_board.cells.lock_mut()[4] = 1;
+/// ```
+/// Tuples are stored in a single `Mutable` in the same way.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Sprite {
+///         position: (f32, f32),
+///     }
+///
+///     mutable_sprite.position.set((1.0, 2.0));
+/// ```
+/// A `Vec` of structs can also be stored as a plain `MutableVec` of mutable structs, which
+/// lets elements be changed in place. Signals of the whole struct only follow changes to
+/// the vector itself though, since the vector's signals produce copies of its elements.
+/// Use a `MutableStructVec` to follow the fields of each element as well.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Leaderboard {
+///         #[mutable_type = "MutableVec<MutablePlayerScore>"] scores: Vec<PlayerScore>,
+///     }
+///
+///     mutable_leaderboard.scores.lock_ref()[0].hits.set(10);
+/// ```
+/// `#[signal_basic]` does the opposite, storing a field in a plain `Mutable` even when it
+/// would otherwise be detected as a nested struct. The field's type only needs to be
+/// `Clone`, which makes it useful for type parameters that are not structs themselves.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Tagged<T> {
+///         #[signal_basic] tag: T,
+///     }
+/// ```
+/// Large values that are expensive to clone can be stored behind a smart pointer with
+/// `#[signal_wrap]`. The field becomes a `Mutable<Arc<T>>`, so its signals only clone the
+/// pointer, while snapshots still contain the plain value.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Upload {
+///         #[signal_wrap = "Arc"] bytes: Vec<u8>,
+///     }
+/// ```
+/// `#[signal_store_as]` stores a basic field as a different type, converting between the
+/// two with `Into`. The `Mutable` and its signals hold the stored type, while snapshots still
+/// contain the original one.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Label {
+///         #[signal_store_as = "String"] text: Cow<'static, str>,
+///     }
+///
+///     mutable_label.text.set("Game over".to_string());
+/// ```
+/// Conversions that `Into` can't express, such as parsing, can name their own functions
+/// instead, with `#[signal_update_with]` converting snapshot values to the stored type and
+/// `#[signal_snapshot_with]` converting a reference to the stored value back.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Setting {
+///         #[signal_store_as = "u32"]
+///         #[signal_update_with = "parse_count"]
+///         #[signal_snapshot_with = "format_count"]
+///         count: String,
+///     }
+/// ```
+/// Snapshots can't borrow from the mutable struct, so reference fields need
+/// `#[signal_store_as]` to name the owned type they are stored as, such as a `String` for a
+/// `&str` or a `Vec<T>` for a `&[T]`. The derive then generates an `Owned<Name>` copy of the
+/// struct with the owned types, which `From` converts the original struct into, and derives
+/// the mutable struct from that, so snapshots are `Owned<Name>` as well.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct View<'a> {
+///         #[signal_store_as = "String"] name: &'a str,
+///     }
+///
+///     let mutable_view = OwnedView::from(View { name: "Ada" }).as_mutable_struct();
+///     mutable_view.update(View { name: "Grace" }.into());
+/// ```
+/// With the `serde` feature enabled, annotating the struct with `#[signal_serde]` also
+/// implements `Serialize` and `Deserialize` for the mutable struct, by way of a snapshot.
+/// ```ignore
+///     #[derive(AsMutableStruct, Serialize, Deserialize)]
+///     #[signal_serde]
+///     struct PlayerScore {
+///         hits: u32,
+///         multiplier: f32,
+///     }
+///
+///     let json = serde_json::to_string(&mutable_score)?;
+///     let restored: MutablePlayerScore = serde_json::from_str(&json)?;
+/// ```
+/// With the `json` feature enabled, `#[signal_json]` adds `snapshot_json()`, which returns
+/// a snapshot as a `serde_json::Value` for logging.
+/// ```ignore
+///     #[derive(AsMutableStruct, Serialize)]
+///     #[signal_json]
+///     struct PlayerScore {
+///         hits: u32,
+///     }
+///
+///     println!("{}", mutable_score.snapshot_json());
+/// ```
+/// Snapshots can keep the fields of a nested struct inline while the mutable struct nests
+/// them, for example to serialize them flat. Fields annotated with
+/// `#[signal_snapshot_flatten = "address: Address"]` are gathered into a single `address`
+/// field holding a `MutableAddress`. `Address` needs a field of the same name and type for
+/// each of them, and no others, since snapshots and updates move the values between the two
+/// structs field by field. The nested field has no attributes of its own, so the gathered
+/// fields can't have any other field attributes either.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct Profile {
+///         name: String,
+///         #[signal_snapshot_flatten = "address: Address"] street: String,
+///         #[signal_snapshot_flatten = "address: Address"] city: String,
+///     }
+///
+///     mutable_profile.address.city.set("Paris".to_string());
+///     assert_eq!(mutable_profile.snapshot().city, "Paris");
+/// ```
+/// Generated code refers to this crate's traits and to futures-signals by the absolute paths
+/// `::futures_signals_structs_traits` and `::futures_signals`. If either dependency is
+/// renamed, `#[signal_crate]` sets the paths to use instead.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     #[signal_crate(traits = "structs_traits", signals = "signals")]
+///     struct PlayerScore {
+///         hits: u32,
+///     }
+/// ```
+/// Annotating the struct with `#[signal_assert_thread_safe]` checks at compile time that the
+/// mutable struct is `Send` and `Sync`.
+///
+/// Basic fields are always checked to be `Clone`, and a field that isn't is reported where
+/// it is declared.
+///
+/// Fields annotated with #[signal_skip] are kept as plain values on the mutable struct.
+/// They are copied into each snapshot, and are left untouched by `update`. A skipped field
+/// that isn't `Clone` still gets the mutable struct, its constructor and its accessors, but
+/// none of the traits and methods that take snapshots.
+/// ```ignore
+///     #[derive(AsMutableStruct)]
+///     struct CachedScore {
+///         points: u32,
+///         #[signal_skip] cached_label: String,
+///     }
+/// ```
+#[proc_macro_derive(
+    AsMutableStruct,
+    attributes(
+        MutableStructName,
+        mutable,
+        mutable_derive,
+        mutable_type,
+        mutable_vis,
+        signal_accessor,
+        signal_accessor_suffix,
+        signal_assert_thread_safe,
+        signal_basic,
+        signal_crate,
+        signal_eq_fn,
+        signal_flatten,
+        signal_hash,
+        signal_json,
+        signal_key,
+        signal_lazy,
+        signal_max,
+        signal_min,
+        signal_rename,
+        signal_serde,
+        signal_skip,
+        signal_snapshot_flatten,
+        signal_snapshot_with,
+        signal_store_as,
+        signal_transparent,
+        signal_update_with,
+        signal_validate,
+        signal_versioned,
+        signal_wrap
+    )
+)]
+pub fn as_mutable_struct(input: TokenStream) -> TokenStream {
+    // Parse the string representation
+    let input: DeriveInput = syn::parse_macro_input!(input);
+
+    expand_as_mutable_struct(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+fn expand_as_mutable_struct(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ast = match input.data {
+        Data::Struct(data) => ItemStruct {
+            attrs: input.attrs,
+            vis: input.vis,
+            struct_token: data.struct_token,
+            ident: input.ident,
+            generics: input.generics,
+            fields: data.fields,
+            semi_token: data.semi_token,
+        },
+        Data::Enum(data) => {
+            return Err(syn::Error::new_spanned(
+                data.enum_token,
+                "AsMutableStruct can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "AsMutableStruct can only be derived for structs",
+            ))
+        }
+    };
+
+    // Snapshots can't borrow from the mutable struct, so a struct with reference fields gets
+    // an owned twin, and the mutable struct is derived from that instead.
+    if let Some((owned_twin, owned_twin_items)) = make_owned_twin(&ast)? {
+        let derived = expand_struct(owned_twin)?;
+        return Ok(quote! {
+            #owned_twin_items
+            #derived
+        });
+    }
+    expand_struct(ast)
+}
+
+fn expand_struct(ast: ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
+    // Generated code refers to the traits crate and futures-signals by their absolute paths,
+    // unless the struct names renamed dependencies with `#[signal_crate]`.
+    let crate_paths = get_crate_paths(&ast)?;
+    CRATE_PATHS.with(|paths| *paths.borrow_mut() = crate_paths);
+
+    // Determine what to name the Mutable version of this struct. Tries to pull from the
+    // MutableStructName attribute, falls back to `MutableStructName` where StructName
+    // is the name of the derived struct.
+    let mutable_name = maybe_get_mutable_name(&ast)?
+        .map(|name| format_ident!("{}", name))
+        .unwrap_or_else(|| format_ident!("Mutable{}", &ast.ident));
+
+    // Extract all fields as MutableStructField instances.
+    let accessor_suffix = maybe_get_accessor_suffix(&ast)?;
+    let mut fields = parse_fields(&ast, &accessor_suffix)?;
+
+    // The mutable struct always shares the visibility of the original struct, since each
+    // is the other's associated type. `mutable_vis` only changes the visibility of its
+    // fields and their accessors.
+    if let Some(mutable_vis) = maybe_get_mutable_vis(&ast)? {
+        for field in fields.iter_mut() {
+            field.set_vis(mutable_vis.clone());
+        }
+    }
+    let own_types = [ast.ident.clone(), mutable_name.clone()];
+    for field in fields.iter_mut() {
+        field.mark_if_recursive(&own_types);
+    }
+
+    // Generic parameters carry over to the mutable struct, along with the bounds its fields
+    // need to be converted. Impls that clone skipped fields are additionally bounded on them
+    // being `Clone`, unlike the struct itself, its constructor and its accessors.
+    let definition_generics = add_field_bounds(&ast.generics, &fields);
+    let generics = add_skipped_clone_bounds(&definition_generics, &fields);
+
+    // Derives forwarded with `mutable_derive` replace the impls that would otherwise be
+    // generated for the same traits.
+    let mutable_derives = maybe_get_mutable_derives(&ast)?;
+    let forwards = |trait_name: &str| {
+        mutable_derives.iter().any(|path| {
+            path.segments
+                .last()
+                .is_some_and(|segment| segment.ident == trait_name)
+        })
+    };
+
+    // Build the impl
+    let gen_mutable = make_mutable_variant(
+        ast.clone(),
+        &definition_generics,
+        &fields,
+        &mutable_name,
+        &mutable_derives,
+        !forwards("Clone"),
+    );
+    let gen_accessors = impl_accessors(&ast, &definition_generics, &fields, &mutable_name);
+    let gen_constructor = impl_constructor(&ast, &definition_generics, &fields, &mutable_name);
+    let gen_consistent_access = impl_consistent_access(&ast, &generics, &fields, &mutable_name);
+    let gen_read_guard = impl_read_guard(&ast, &generics, &fields, &mutable_name);
+    let gen_read_only = make_read_only(&ast, &generics, &fields, &mutable_name);
+    let gen_debug = if forwards("Debug") {
+        quote!()
+    } else {
+        impl_debug(&ast, &generics, &fields, &mutable_name)
+    };
+    let gen_field_debug = impl_field_debug(&ast, &generics, &fields, &mutable_name);
+    let gen_partial_eq = if forwards("PartialEq") {
+        quote!()
+    } else {
+        impl_partial_eq(&generics, &fields, &mutable_name)
+    };
+    let gen_eq_and_hash = if forwards("Eq") || forwards("Hash") {
+        quote!()
+    } else {
+        impl_eq_and_hash(&ast, &generics, &fields, &mutable_name)
+    };
+    let gen_default = if forwards("Default") {
+        quote!()
+    } else {
+        impl_default(&ast, &generics, &mutable_name)
+    };
+    let gen_partial_update = impl_partial_update(&ast, &generics, &fields, &mutable_name);
+    let gen_try_update = impl_try_update(&ast, &generics, &mutable_name)?;
+    let gen_keyed = impl_keyed(&ast, &generics, &fields)?;
+    let gen_flatten = impl_flatten(&ast, &generics, &fields, &mutable_name)?;
+    let gen_diff = impl_diff(&ast, &generics, &fields, &mutable_name);
+    let gen_set_field = impl_set_field(&ast, &generics, &fields, &mutable_name);
+    let gen_apply_diff = impl_apply_diff(&ast, &generics, &fields, &mutable_name);
+    let gen_field_signals = impl_field_signals(&ast, &generics, &fields, &mutable_name);
+    let gen_builder = make_builder(&ast, &generics, &fields, &mutable_name);
+    let gen_from = impl_from(&ast, &generics, &mutable_name);
+    let gen_serde = impl_serde(&ast, &generics, &mutable_name)?;
+    let gen_snapshot_json = impl_snapshot_json(&ast, &generics, &mutable_name)?;
+    let gen_thread_safe_assertion = make_thread_safe_assertion(&ast, &mutable_name)?;
+    let gen_clone_assertions = make_clone_assertions(&generics, &fields);
+    let gen_mutable_type_assertions = make_mutable_type_assertions(&generics, &fields);
+    let gen_as_signal_struct = impl_as_signal_struct(ast, &generics, &fields, &mutable_name);
+
+    // Return the generated impl
+    Ok(quote! {
+        #gen_mutable
+        #gen_accessors
+        #gen_constructor
+        #gen_consistent_access
+        #gen_read_guard
+        #gen_read_only
+        #gen_debug
+        #gen_field_debug
+        #gen_partial_eq
+        #gen_eq_and_hash
+        #gen_default
+        #gen_partial_update
+        #gen_try_update
+        #gen_keyed
+        #gen_flatten
+        #gen_diff
+        #gen_set_field
+        #gen_apply_diff
+        #gen_field_signals
+        #gen_builder
+        #gen_from
+        #gen_serde
+        #gen_snapshot_json
+        #gen_thread_safe_assertion
+        #gen_clone_assertions
+        #gen_mutable_type_assertions
+        #gen_as_signal_struct
+    })
+}
+
+fn make_mutable_variant(
+    input: ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+    mutable_derives: &[syn::Path],
+    implement_clone: bool,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let signals = signals_crate();
+    let version_member = get_version_member(&input);
+    let version_init = make_version_init(&input);
+    let version_bump = make_version_bump(&input);
+    let original_ident = input.ident;
+    let original_vis = input.vis;
+    // `#[non_exhaustive]` carries over, so other crates can't build the mutable struct with
+    // a literal either. Code generated here is part of the defining crate, so it still can.
+    let docs = input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc") || attr.path.is_ident("non_exhaustive"));
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let definition_where_clause = make_where_clause(generics, quote!());
+    let generics = &add_skipped_clone_bounds(generics, fields);
+    let where_clause = make_where_clause(generics, quote!());
+
+    let mutable_fields = fields
+        .iter()
+        .map(MutableStructField::get_mutable_field_definition)
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
+    let snapshot = make_snapshot_literal(
+        &original_ident,
+        fields,
+        MutableStructField::get_snapshot_generator,
+    );
+
+    let snapshot_into_fields = fields
+        .iter()
+        .map(MutableStructField::get_snapshot_into)
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
+    let update_fields = fields
+        .iter()
+        .filter_map(|field| field.get_update_setter(format_ident!("new_snapshot")))
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
+    let update_ref_fields = fields
+        .iter()
+        .filter_map(|field| field.get_update_ref_setter(format_ident!("new_snapshot")))
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
+    let merged_fields = fields
+        .iter()
+        .filter_map(MutableStructField::get_merger)
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
+    let replaced = make_snapshot_literal(&original_ident, fields, |field| {
+        field.get_replacer(format_ident!("new_snapshot"))
+    });
+
+    let cloned_fields = fields
+        .iter()
+        .map(|field| {
+            let name = field.get_name();
+            let cloner = field.get_cloner();
+            quote!(#name: #cloner)
+        })
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
+    let version = version_member.as_ref().map(|member| quote!(self.#member));
+    let replace_with_body = if version.is_some() {
+        quote! {
+            let previous = #replaced;
+            #version_bump
+            previous
+        }
+    } else {
+        replaced
+    };
+    let snapshot_signal = hold_signal(
+        version.as_ref(),
+        make_snapshot_signal(&original_ident, fields),
+    );
+
+    let derive_attribute = if mutable_derives.is_empty() {
+        quote!()
+    } else {
+        quote!(#[derive(#(#mutable_derives),*)])
+    };
+
+    // Unit structs produce a mutable unit struct. Constructors in the generated code always
+    // use braces, which unit structs accept as well. Versioned structs keep their version in
+    // a hidden field, so a versioned unit struct gets braces too.
+    let version_type = quote!(#traits::version::StructVersion);
+    let struct_definition = match (&input.fields, &version_member) {
+        (Fields::Unnamed(_), Some(_)) => quote! {
+            #(#docs)*
+            #original_vis struct #mutable_name #generics (#(#mutable_fields,)* #version_type) #definition_where_clause;
+        },
+        (Fields::Unnamed(_), None) => quote! {
+            #(#docs)*
+            #original_vis struct #mutable_name #generics (#(#mutable_fields),*) #definition_where_clause;
+        },
+        (Fields::Unit, None) => quote! {
+            #(#docs)*
+            #original_vis struct #mutable_name #generics #definition_where_clause;
+        },
+        (_, Some(version_member)) => quote! {
+            #(#docs)*
+            #original_vis struct #mutable_name #generics #definition_where_clause {
+                #(#mutable_fields,)*
+                #version_member: #version_type
+            }
+        },
+        (Fields::Named(_), None) => quote! {
+            #(#docs)*
+            #original_vis struct #mutable_name #generics #definition_where_clause {
+                #(#mutable_fields),*
+            }
+        },
+    };
+    let versioned_impl = match &version_member {
+        Some(version_member) => quote! {
+            impl #impl_generics #traits::Versioned for #mutable_name #ty_generics #where_clause {
+                fn struct_version(&self) -> &#version_type {
+                    &self.#version_member
+                }
+            }
+        },
+        None => quote!(),
+    };
+
+    // Nested structs are copied through their snapshots, so only the values stored in the
+    // struct need to be `Clone` for the impl to exist.
+    let clone_where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            fields
+                .iter()
+                .filter(|field| !matches!(field, MutableStructField::MutableStruct { .. }))
+                .map(MutableStructField::get_value_type),
+            quote!(::std::clone::Clone),
+        ),
+    );
+    let clone_impl = if implement_clone {
+        quote! {
+            impl #impl_generics Clone for #mutable_name #ty_generics #clone_where_clause {
+                fn clone(&self) -> Self {
+                    #mutable_name {
+                        #(#cloned_fields,)*
+                        #version_init
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    quote! {
+        #derive_attribute
+        #[allow(non_snake_case)]
+        #struct_definition
+
+        #[allow(non_snake_case)]
+        impl #impl_generics #traits::MutableStruct
+            for #mutable_name #ty_generics #where_clause
+        {
+            type SnapshotType = #original_ident #ty_generics;
+
+            fn snapshot(&self) -> #original_ident #ty_generics {
+                #snapshot
+            }
+
+            #[allow(unused_variables)]
+            fn snapshot_into(&self, out: &mut #original_ident #ty_generics) {
+                #(#snapshot_into_fields;)*
+            }
+
+            fn from_snapshot(snapshot: &#original_ident #ty_generics) -> Self {
+                #traits::AsMutableStruct::as_mutable_struct(snapshot)
+            }
+
+            fn update(&self, new_snapshot: #original_ident #ty_generics) {
+                #(#update_fields;)*
+                #version_bump
+            }
+
+            #[allow(unused_variables)]
+            fn update_ref(&self, new_snapshot: &#original_ident #ty_generics) {
+                #(#update_ref_fields;)*
+                #version_bump
+            }
+
+            #[allow(unused_variables)]
+            fn merge(&self, other: &Self) {
+                #(#merged_fields;)*
+                #version_bump
+            }
+
+            fn replace_with(&self, new_snapshot: #original_ident #ty_generics) -> #original_ident #ty_generics {
+                #replace_with_body
+            }
+
+            fn snapshot_signal(
+                &self,
+            ) -> impl #signals::signal::Signal<Item = #original_ident #ty_generics> + 'static {
+                #snapshot_signal
+            }
+        }
+
+        #clone_impl
+        #versioned_impl
+    }
+}
+
+/// Returns code that combines every field's signal into a signal of whole snapshots.
+/// Skipped fields never change, so their current values are captured up front.
+fn make_snapshot_signal(
+    original_ident: &Ident,
+    fields: &[MutableStructField],
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let signals = signals_crate();
+    let signal_bindings = fields
+        .iter()
+        .filter_map(|field| {
+            let binding = field.get_binding();
+            field
+                .get_snapshot_signal()
+                .map(|signal| quote!(let #binding = #signal))
+        })
+        .collect::<Vec<proc_macro2::TokenStream>>();
+    if signal_bindings.is_empty() {
+        return quote!(#signals::signal::always(
+            #traits::MutableStruct::snapshot(self)
+        ));
+    }
+
+    let captured_fields = fields.iter().filter_map(|field| match field {
+        MutableStructField::Skipped { name, .. } => {
+            let binding = field.get_binding();
+            Some(quote!(let #binding = self.#name.clone();))
+        }
+        _ => None,
+    });
+    let snapshot = make_snapshot_literal(original_ident, fields, |field| {
+        let binding = field.get_binding();
+        match field {
+            MutableStructField::Skipped { .. } => quote!(::std::clone::Clone::clone(&#binding)),
+            _ => quote!(::std::clone::Clone::clone(#binding)),
+        }
+    });
+
+    quote! {{
+        #(#captured_fields)*
+        #signals::map_ref! {
+            #(#signal_bindings),* =>
+            #snapshot
+        }
+    }}
+}
+
+/// Adds the per-field signal accessors to the mutable struct.
+fn impl_accessors(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let version = get_version_member(input).map(|member| quote!(self.#member));
+    let accessors = fields
+        .iter()
+        .map(|field| field.get_accessors(version.as_ref()));
+    quote! {
+        #[allow(non_snake_case)]
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            #(#accessors)*
+        }
+    }
+}
+
+/// Adds a `new` constructor to the mutable struct that takes the value of every field in
+/// declaration order, so it can be built without going through a snapshot. Structs marked
+/// `#[non_exhaustive]` get none, since adding a field would change its arguments.
+fn impl_constructor(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    if has_flag(&input.attrs, "non_exhaustive") {
+        return quote!();
+    }
+    let original_vis = &input.vis;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let arguments = fields.iter().map(|field| {
+        let binding = field.get_binding();
+        let ty = field.get_type();
+        quote!(#binding: #ty)
+    });
+    let mutable_fields = fields.iter().map(|field| {
+        let name = field.get_name();
+        let constructor = field.get_value_constructor(field.get_binding().to_token_stream());
+        quote!(#name: #constructor)
+    });
+    let version_init = make_version_init(input);
+
+    quote! {
+        #[allow(non_snake_case)]
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            #[allow(clippy::too_many_arguments)]
+            #original_vis fn new(#(#arguments),*) -> Self {
+                #mutable_name {
+                    #(#mutable_fields,)*
+                    #version_init
+                }
+            }
+        }
+    }
+}
+
+/// Generates a `ReadOnly<MutableName>` handle that shares a mutable struct but only exposes
+/// its signals and snapshots, along with `freeze` to create one.
+fn make_read_only(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let signals = signals_crate();
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let read_only_name = format_ident!("ReadOnly{}", mutable_name);
+    let read_only_doc = format!(
+        "A read-only handle to a [`{}`], which can observe and snapshot the struct but not change it.",
+        mutable_name
+    );
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let version = get_version_member(input).map(|member| quote!(self.0.#member));
+    let signal_accessors = fields
+        .iter()
+        .map(|field| field.get_signal_accessors(quote!(self.0), version.as_ref()));
+
+    quote! {
+        #[doc = #read_only_doc]
+        #original_vis struct #read_only_name #generics (::std::sync::Arc<#mutable_name #ty_generics>) #where_clause;
+
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            /// Turns this struct into a read-only handle. To keep changing the struct while
+            /// others observe it, share it in an `Arc` and convert that with `From` instead.
+            #original_vis fn freeze(self) -> #read_only_name #ty_generics {
+                #read_only_name(::std::sync::Arc::new(self))
+            }
+        }
+
+        impl #impl_generics ::std::convert::From<::std::sync::Arc<#mutable_name #ty_generics>>
+            for #read_only_name #ty_generics #where_clause
+        {
+            fn from(mutable: ::std::sync::Arc<#mutable_name #ty_generics>) -> Self {
+                #read_only_name(mutable)
+            }
+        }
+
+        impl #impl_generics ::std::clone::Clone for #read_only_name #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                #read_only_name(::std::sync::Arc::clone(&self.0))
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl #impl_generics #read_only_name #ty_generics #where_clause {
+            /// Returns a snapshot of the current value of every field.
+            #[must_use]
+            #original_vis fn snapshot(&self) -> #original_ident #ty_generics {
+                #traits::MutableStruct::snapshot(&*self.0)
+            }
+
+            /// Returns a signal that produces a new snapshot whenever any field changes.
+            // The trait's signal borrows the struct in its type, even though it is 'static,
+            // so the `'_` bound has to capture that lifetime as well.
+            #original_vis fn snapshot_signal(
+                &self,
+            ) -> impl #signals::signal::Signal<Item = #original_ident #ty_generics>
+                   + 'static
+                   + '_ {
+                #traits::MutableStruct::snapshot_signal(&*self.0)
+            }
+
+            #(#signal_accessors)*
+        }
+    }
+}
+
+/// Adds `consistent_snapshot` and `consistent_update`, which hold the locks of every basic
+/// field at once. Locks are always taken in field order, so the two can't deadlock each
+/// other. Nested structs are read and written while those locks are held, but are not
+/// covered by them.
+fn impl_consistent_access(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let version_bump = make_version_bump(input);
+
+    let guard_name = |field: &MutableStructField| format_ident!("{}_guard", field.get_binding());
+    let basic_fields = fields
+        .iter()
+        .filter(|field| matches!(field, MutableStructField::Basic { .. }))
+        .collect::<Vec<&MutableStructField>>();
+    let read_guards = basic_fields.iter().map(|field| {
+        let name = field.get_name();
+        let guard = guard_name(field);
+        quote!(let #guard = self.#name.lock_ref();)
+    });
+    let write_guards = basic_fields.iter().map(|field| {
+        let name = field.get_name();
+        let guard = guard_name(field);
+        quote!(let mut #guard = self.#name.lock_mut();)
+    });
+    let snapshot = make_snapshot_literal(original_ident, fields, |field| match field {
+        MutableStructField::Basic { .. } => {
+            let guard = guard_name(field);
+            field.unwrap_value(quote!(&*#guard))
+        }
+        _ => field.get_snapshot_generator(),
+    });
+    let update_fields = fields.iter().filter_map(|field| match field {
+        MutableStructField::Basic { source_name, .. } => {
+            let guard = guard_name(field);
+            let value = field.wrap_value(quote!(new_snapshot.#source_name));
+            Some(quote!(*#guard = #value;))
+        }
+        _ => field
+            .get_update_setter(format_ident!("new_snapshot"))
+            .map(|setter| quote!(#setter;)),
+    });
+
+    quote! {
+        #[allow(non_snake_case)]
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            /// Returns a snapshot while holding the lock of every field, so it can't observe
+            /// a `consistent_update` halfway through. `snapshot` reads fields one at a time,
+            /// so it may mix old and new values when other threads are writing.
+            #original_vis fn consistent_snapshot(&self) -> #original_ident #ty_generics {
+                #(#read_guards)*
+                #snapshot
+            }
+
+            /// Updates every field while holding the lock of every field, so that
+            /// `consistent_snapshot` sees either all or none of the changes.
+            #original_vis fn consistent_update(&self, new_snapshot: #original_ident #ty_generics) {
+                {
+                    #(#write_guards)*
+                    #(#update_fields)*
+                }
+                #version_bump
+            }
+        }
+    }
+}
+
+/// Generates a `<Name>ReadGuard` struct holding the read lock of every basic field, and a
+/// `read` method returning one, so that several fields can be read together without
+/// cloning them. Locks are taken in field order, like in `consistent_snapshot`. Nested
+/// structs and skipped fields are borrowed rather than locked. Structs without
+/// fields have nothing to read, and get neither.
+fn impl_read_guard(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    if fields.is_empty() {
+        return quote!();
+    }
+    let signals = signals_crate();
+    let original_vis = &input.vis;
+    let guard_name = format_ident!("{}ReadGuard", input.ident);
+    let guard_doc = format!(
+        "The current values of the fields of [`{}`], returned by `read`. Basic fields stay locked until it is dropped.",
+        mutable_name
+    );
+    let mut guard_generics = generics.clone();
+    guard_generics.params.insert(0, syn::parse_quote!('__guard));
+    let (_, guard_ty_generics, _) = guard_generics.split_for_impl();
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+
+    let guard_fields = fields.iter().map(|field| {
+        let binding = field.get_binding();
+        let vis = field.get_vis();
+        let field_type = match field {
+            MutableStructField::Basic { .. } => {
+                let value_type = field.get_value_type();
+                quote!(#signals::signal::MutableLockRef<'__guard, #value_type>)
+            }
+            _ => {
+                let mutable_type = field.get_mutable_type();
+                quote!(&'__guard #mutable_type)
+            }
+        };
+        quote!(#vis #binding: #field_type)
+    });
+    let guard_values = fields.iter().map(|field| {
+        let name = field.get_name();
+        let binding = field.get_binding();
+        match field {
+            MutableStructField::Basic { .. } => quote!(#binding: self.#name.lock_ref()),
+            _ => quote!(#binding: &self.#name),
+        }
+    });
+
+    quote! {
+        #[doc = #guard_doc]
+        #[allow(non_snake_case)]
+        #original_vis struct #guard_name #guard_generics #where_clause {
+            #(#guard_fields),*
+        }
+
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            /// Locks every basic field for reading and returns their values, which can be
+            /// read in one scope without cloning them. Writes to those fields wait until the
+            /// guard is dropped.
+            #original_vis fn read<'__guard>(&'__guard self) -> #guard_name #guard_ty_generics {
+                #guard_name {
+                    #(#guard_values),*
+                }
+            }
+        }
+    }
+}
+
+/// Generates a `<Name>FieldDiff` enum naming each field, and implements `Diff` for the
+/// mutable struct using it. The impl is only available when every compared field can be
+/// compared.
+fn impl_diff(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let field_enum = format_ident!("{}FieldDiff", original_ident);
+    let field_enum_doc = format!(
+        "Names a field of [`{}`] that differs from a snapshot.",
+        mutable_name
+    );
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+
+    let nested_bounds = fields.iter().filter_map(|field| match field {
+        MutableStructField::MutableStruct { .. } => field.get_diff_bound(),
+        _ => None,
+    });
+    let enum_where_clause = make_where_clause(generics, make_predicates(nested_bounds));
+    // The derived impls on the enum require its type parameters to implement the same
+    // traits, so the `Diff` impl is only available when they do.
+    let enum_bound = quote!(
+        #field_enum #ty_generics: ::std::fmt::Debug + ::std::clone::Clone + ::std::cmp::PartialEq
+    );
+    let impl_where_clause = make_where_clause(
+        generics,
+        make_predicates(
+            fields
+                .iter()
+                .filter_map(MutableStructField::get_diff_bound)
+                .chain(std::iter::once(enum_bound)),
+        ),
+    );
+
+    let phantom_variant = make_phantom_variant(generics);
+    let variants = fields
+        .iter()
+        .filter_map(MutableStructField::get_diff_variant);
+    let diff_checks = fields
+        .iter()
+        .filter_map(|field| field.get_diff_check(&field_enum))
+        .map(|check| quote!({ #check }));
+
+    quote! {
+        #[doc = #field_enum_doc]
+        #[derive(Debug, Clone, PartialEq)]
+        #original_vis enum #field_enum #generics #enum_where_clause {
+            #(#variants,)*
+            #phantom_variant
+        }
+
+        impl #impl_generics #traits::Diff
+            for #mutable_name #ty_generics #impl_where_clause
+        {
+            type Field = #field_enum #ty_generics;
+
+            fn diff(&self, other: &#original_ident #ty_generics) -> ::std::vec::Vec<Self::Field> {
+                #[allow(unused_mut)]
+                let mut changes = ::std::vec::Vec::new();
+                #(#diff_checks)*
+                changes
+            }
+        }
+    }
+}
+
+/// Generates a `<Name>Field` enum naming each field that is stored in the mutable struct,
+/// a `<Name>FieldValue` enum holding a value for any one of them, and a `set_field` method
+/// that stores such a value in the matching field.
+fn impl_set_field(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let field_enum = format_ident!("{}Field", original_ident);
+    let value_enum = format_ident!("{}FieldValue", original_ident);
+    let field_enum_doc = format!(
+        "Names a field of [`{}`] that can be set with `set_field`.",
+        mutable_name
+    );
+    let value_enum_doc = format!("A new value for one of the fields of [`{}`].", mutable_name);
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let phantom_variant = make_phantom_variant(generics);
+
+    let settable_fields = fields
+        .iter()
+        .filter(|field| !matches!(field, MutableStructField::Skipped { .. }))
+        .collect::<Vec<&MutableStructField>>();
+    let field_variants = settable_fields
+        .iter()
+        .map(|field| field.get_variant_name())
+        .collect::<Vec<Ident>>();
+    let value_variants = settable_fields.iter().map(|field| {
+        let variant_name = field.get_variant_name();
+        let ty = field.get_type();
+        quote!(#variant_name(#ty))
+    });
+    let version_bump = make_version_bump(input);
+    let setters = settable_fields.iter().map(|field| {
+        let name = field.get_name();
+        let variant_name = field.get_variant_name();
+        let setter = match field {
+            MutableStructField::Basic { .. } => {
+                let value = field.wrap_value(quote!(value));
+                quote!(self.#name.set(#value))
+            }
+            _ => {
+                let nested_trait = field.get_nested_trait();
+                quote!(#nested_trait::update(&self.#name, value))
+            }
+        };
+        quote! {
+            (#field_enum::#variant_name, #value_enum::#variant_name(value)) => {
+                #setter;
+                #version_bump
+                ::std::result::Result::Ok(())
+            }
+        }
+    });
+    let value_fields = field_variants
+        .iter()
+        .map(|variant_name| quote!(#value_enum::#variant_name(_) => #field_enum::#variant_name));
+    let phantom_value_field = if phantom_variant.is_empty() {
+        quote!()
+    } else {
+        quote!(#value_enum::__Unused(_, never) => match never {},)
+    };
+    // Debug and PartialEq are only implemented when every field supports them, so that
+    // fields such as trait objects can still be set.
+    let debug_where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            settable_fields
+                .iter()
+                .map(|field| field.get_type().to_token_stream()),
+            quote!(::std::fmt::Debug),
+        ),
+    );
+    let partial_eq_where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            settable_fields
+                .iter()
+                .map(|field| field.get_type().to_token_stream()),
+            quote!(::std::cmp::PartialEq),
+        ),
+    );
+    let debug_variants = field_variants.iter().map(|variant_name| {
+        let variant_string = variant_name.to_string();
+        quote! {
+            #value_enum::#variant_name(ref value) => {
+                f.debug_tuple(#variant_string).field(value).finish()
+            }
+        }
+    });
+    let eq_variants = field_variants.iter().map(|variant_name| {
+        quote!((#value_enum::#variant_name(value), #value_enum::#variant_name(other)) => value == other)
+    });
+    let phantom_debug = if phantom_variant.is_empty() {
+        quote!()
+    } else {
+        quote!(#value_enum::__Unused(_, never) => match never {},)
+    };
+    let phantom_eq = if phantom_variant.is_empty() {
+        quote!()
+    } else {
+        quote!((#value_enum::__Unused(_, never), _) => match *never {},)
+    };
+
+    quote! {
+        #[doc = #field_enum_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #original_vis enum #field_enum {
+            #(#field_variants,)*
+        }
+
+        #[doc = #value_enum_doc]
+        #[derive(Clone)]
+        #original_vis enum #value_enum #generics #where_clause {
+            #(#value_variants,)*
+            #phantom_variant
+        }
+
+        impl #impl_generics ::std::fmt::Debug for #value_enum #ty_generics #debug_where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    #(#debug_variants)*
+                    #phantom_debug
+                }
+            }
+        }
+
+        impl #impl_generics ::std::cmp::PartialEq for #value_enum #ty_generics #partial_eq_where_clause {
+            fn eq(&self, other: &Self) -> bool {
+                #[allow(unreachable_patterns)]
+                match (self, other) {
+                    #(#eq_variants,)*
+                    #phantom_eq
+                    _ => false,
+                }
+            }
+        }
+
+        impl #impl_generics #value_enum #ty_generics #where_clause {
+            /// Returns the field this value belongs to.
+            #original_vis fn field(&self) -> #field_enum {
+                match *self {
+                    #(#value_fields,)*
+                    #phantom_value_field
+                }
+            }
+        }
+
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            /// Stores `value` in `field`, notifying its subscribers. If `value` belongs to a
+            /// different field, nothing is changed and the value is handed back.
+            #original_vis fn set_field(
+                &self,
+                field: #field_enum,
+                value: #value_enum #ty_generics,
+            ) -> ::std::result::Result<(), #value_enum #ty_generics> {
+                #[allow(unreachable_patterns)]
+                match (field, value) {
+                    #(#setters)*
+                    (_, value) => ::std::result::Result::Err(value),
+                }
+            }
+        }
+    }
+}
+
+/// Generates an `Applyable<Name>` patch struct holding an `Option` of a new value for each
+/// field, and implements `ApplyDiff` so that only the fields that are `Some` get set.
+/// Nested structs are patched with their own patch type, except for recursive fields and
+/// vectors of structs, which take a whole snapshot.
+fn impl_apply_diff(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let version_bump = make_version_bump(input);
+    let patch_name = format_ident!("Applyable{}", original_ident);
+    let patch_doc = format!(
+        "New values for some of the fields of [`{}`], applied with `apply_diff`.",
+        mutable_name
+    );
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+
+    let patched_fields = fields
+        .iter()
+        .filter(|field| !matches!(field, MutableStructField::Skipped { .. }))
+        .collect::<Vec<&MutableStructField>>();
+    let uses_nested_patch = |field: &MutableStructField| {
+        matches!(
+            field,
+            MutableStructField::MutableStruct {
+                element_type: None,
+                recursive: false,
+                ..
+            }
+        )
+    };
+    let where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            patched_fields
+                .iter()
+                .filter(|field| uses_nested_patch(field))
+                .map(|field| field.get_mutable_type()),
+            quote!(#traits::ApplyDiff),
+        ),
+    );
+
+    let patch_types = patched_fields
+        .iter()
+        .map(|field| {
+            if uses_nested_patch(field) {
+                let mutable_type = field.get_mutable_type();
+                quote!(::std::option::Option<<#mutable_type as #traits::ApplyDiff>::Patch>)
+            } else {
+                let ty = field.get_type();
+                quote!(::std::option::Option<#ty>)
+            }
+        })
+        .collect::<Vec<proc_macro2::TokenStream>>();
+    let patch_members = patched_fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match field.get_name() {
+            Member::Named(ident) => Member::Named(ident.clone()),
+            Member::Unnamed(_) => Member::Unnamed(index.into()),
+        })
+        .collect::<Vec<Member>>();
+    let phantom_type = make_phantom_type(generics);
+    let phantom_member = match input.fields {
+        Fields::Named(_) => Member::Named(format_ident!("__unused")),
+        _ => Member::Unnamed(patched_fields.len().into()),
+    };
+
+    let patch_definition = match input.fields {
+        Fields::Named(_) => {
+            let definitions = patched_fields
+                .iter()
+                .zip(&patch_types)
+                .map(|(field, patch_type)| {
+                    let vis = field.get_vis();
+                    let name = field.get_name();
+                    quote!(#vis #name: #patch_type)
+                });
+            let phantom_definition = phantom_type.as_ref().map(|phantom_type| {
+                quote! {
+                    #[doc(hidden)]
+                    #original_vis __unused: #phantom_type,
+                }
+            });
+            quote! {
+                #[allow(non_snake_case)]
+                #original_vis struct #patch_name #generics #where_clause {
+                    #(#definitions,)*
+                    #phantom_definition
+                }
+            }
+        }
+        _ => {
+            let definitions = patched_fields
+                .iter()
+                .zip(&patch_types)
+                .map(|(field, patch_type)| {
+                    let vis = field.get_vis();
+                    quote!(#vis #patch_type)
+                });
+            let phantom_definition = phantom_type
+                .as_ref()
+                .map(|phantom_type| quote!(#[doc(hidden)] #original_vis #phantom_type,));
+            quote! {
+                #original_vis struct #patch_name #generics (
+                    #(#definitions,)*
+                    #phantom_definition
+                ) #where_clause;
+            }
+        }
+    };
+    let phantom_default = phantom_type
+        .as_ref()
+        .map(|_| quote!(#phantom_member: ::std::marker::PhantomData,));
+
+    let appliers = patched_fields
+        .iter()
+        .zip(&patch_members)
+        .map(|(field, member)| {
+            let name = field.get_name();
+            let setter = match field {
+                MutableStructField::Basic { .. } => {
+                    let value = field.wrap_value(quote!(value));
+                    quote!(self.#name.set(#value))
+                }
+                _ if uses_nested_patch(field) => {
+                    quote!(#traits::ApplyDiff::apply_diff(&self.#name, value))
+                }
+                _ => {
+                    let nested_trait = field.get_nested_trait();
+                    quote!(#nested_trait::update(&self.#name, value))
+                }
+            };
+            quote! {
+                if let ::std::option::Option::Some(value) = patch.#member {
+                    #setter;
+                }
+            }
+        });
+
+    quote! {
+        #[doc = #patch_doc]
+        #patch_definition
+
+        impl #impl_generics ::std::default::Default for #patch_name #ty_generics #where_clause {
+            fn default() -> Self {
+                #patch_name {
+                    #(#patch_members: ::std::option::Option::None,)*
+                    #phantom_default
+                }
+            }
+        }
+
+        impl #impl_generics #traits::ApplyDiff
+            for #mutable_name #ty_generics #where_clause
+        {
+            type Patch = #patch_name #ty_generics;
+
+            #[allow(unused_variables)]
+            fn apply_diff(&self, patch: Self::Patch) {
+                #(#appliers)*
+                #version_bump
+            }
+        }
+    }
+}
+
+/// Generates a `<name>_fields` module holding a marker type for each field that is stored
+/// in the mutable struct, and implements `GetFieldSignal` for each of them.
+fn impl_field_signals(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let signals = signals_crate();
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+
+    let version = get_version_member(input).map(|member| quote!(self.#member));
+    let fields_module = format_ident!("{}_fields", to_snake_case(&original_ident.to_string()));
+    let fields_module_doc = format!(
+        "Markers that select a field of [`{0}`](super::{0}) in `get_field_signal`.",
+        mutable_name
+    );
+    let selected_fields = fields
+        .iter()
+        .filter_map(|field| Some((field, field.get_snapshot_signal()?)))
+        .collect::<Vec<_>>();
+    if selected_fields.is_empty() {
+        return quote!();
+    }
+
+    let markers = selected_fields.iter().map(|(field, _)| {
+        let marker = field.get_variant_name();
+        let marker_doc = format!(
+            "Selects the `{}` field of [`{1}`](super::{1}) in `get_field_signal`.",
+            field.get_name().to_token_stream(),
+            mutable_name
+        );
+        quote! {
+            #[doc = #marker_doc]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+            pub struct #marker;
+        }
+    });
+    let impls = selected_fields.iter().map(|(field, snapshot_signal)| {
+        let snapshot_signal = hold_signal(version.as_ref(), snapshot_signal.clone());
+        let marker = field.get_variant_name();
+        let ty = field.get_type();
+        quote! {
+            impl #impl_generics #traits::GetFieldSignal<#fields_module::#marker>
+                for #mutable_name #ty_generics #where_clause
+            {
+                type Value = #ty;
+
+                fn get_field_signal(
+                    &self,
+                    _field: #fields_module::#marker,
+                ) -> impl #signals::signal::Signal<Item = Self::Value> + 'static {
+                    #snapshot_signal
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #fields_module_doc]
+        #original_vis mod #fields_module {
+            #(#markers)*
+        }
+
+        #(#impls)*
+    }
+}
+
+/// Converts a `CamelCase` struct name into `snake_case`, for naming generated modules.
+fn to_snake_case(name: &str) -> String {
+    let mut snake_case = String::new();
+    for (index, c) in name.trim_start_matches("r#").char_indices() {
+        if c.is_ascii_uppercase() {
+            if index > 0 && !snake_case.ends_with('_') {
+                snake_case.push('_');
+            }
+            snake_case.push(c.to_ascii_lowercase());
+        } else {
+            snake_case.push(c);
+        }
+    }
+    snake_case
+}
+
+/// Implements `Debug` for the mutable struct, printing the current value of each field.
+/// The impl is only available when every field can be printed.
+fn impl_debug(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let mutable_name_str = mutable_name.to_string();
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            fields
+                .iter()
+                .map(MutableStructField::get_bounded_value_type),
+            quote!(::std::fmt::Debug),
+        ),
+    );
+
+    let debug_fields = fields.iter().map(|field| {
+        let value = field.get_value_ref(quote!(self));
+        match field.get_name() {
+            Member::Named(ident) => {
+                let name = ident.to_string();
+                quote!(.field(#name, #value))
+            }
+            Member::Unnamed(_) => quote!(.field(#value)),
+        }
+    });
+    let builder = if let Fields::Unnamed(_) = input.fields {
+        quote!(debug_tuple)
+    } else {
+        quote!(debug_struct)
+    };
+
+    quote! {
+        impl #impl_generics ::std::fmt::Debug for #mutable_name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.#builder(#mutable_name_str)
+                    #(#debug_fields)*
+                    .finish()
+            }
+        }
+    }
+}
+
+/// Adds `field_names` and `field_debug_values`, which list every field of the mutable
+/// struct for generic inspectors. Positional fields are named by their index.
+fn impl_field_debug(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let original_vis = &input.vis;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let debug_bounds = make_bounds(
+        fields
+            .iter()
+            .map(MutableStructField::get_bounded_value_type),
+        quote!(::std::fmt::Debug),
+    );
+
+    let field_names = fields
+        .iter()
+        .map(|field| match field.get_name() {
+            Member::Named(ident) => ident.to_string().trim_start_matches("r#").to_string(),
+            Member::Unnamed(index) => index.index.to_string(),
+        })
+        .collect::<Vec<String>>();
+    let debug_values = fields.iter().zip(&field_names).map(|(field, name)| {
+        let value = field.get_value_ref(quote!(self));
+        quote!((#name, ::std::format!("{:?}", #value)))
+    });
+
+    quote! {
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            /// Returns the name of every field, in declaration order.
+            #original_vis fn field_names() -> &'static [&'static str] {
+                &[#(#field_names),*]
+            }
+
+            /// Returns the name of every field along with its current value formatted with
+            /// `Debug`, in declaration order.
+            #original_vis fn field_debug_values(
+                &self,
+            ) -> ::std::vec::Vec<(&'static str, ::std::string::String)>
+            where
+                #debug_bounds
+            {
+                ::std::vec![#(#debug_values),*]
+            }
+        }
+    }
+}
+
+/// Implements `PartialEq` for the mutable struct by comparing the current value of each
+/// field. The impl is only available when every field can be compared.
+fn impl_partial_eq(
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            fields
+                .iter()
+                .map(MutableStructField::get_bounded_value_type),
+            quote!(::std::cmp::PartialEq),
+        ),
+    );
+
+    let comparisons = fields.iter().map(|field| {
+        let own_value = field.get_comparable_value_ref(quote!(self));
+        let other_value = field.get_comparable_value_ref(quote!(other));
+        quote!(#own_value == #other_value)
+    });
+
+    quote! {
+        impl #impl_generics ::std::cmp::PartialEq for #mutable_name #ty_generics #where_clause {
+            fn eq(&self, other: &Self) -> bool {
+                true #(&& #comparisons)*
+            }
+        }
+    }
+}
+
+/// Implements `Eq` and `Hash` for the mutable struct over the current value of each field,
+/// if the struct opts in with `#[signal_hash]`. The impls are only available when every
+/// field supports them.
+fn impl_eq_and_hash(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    if !has_flag(&input.attrs, "signal_hash") {
+        return quote!();
+    }
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let eq_where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            fields
+                .iter()
+                .map(MutableStructField::get_bounded_value_type),
+            quote!(::std::cmp::Eq),
+        ),
+    );
+    let hash_where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            fields
+                .iter()
+                .map(MutableStructField::get_bounded_value_type),
+            quote!(::std::hash::Hash),
+        ),
+    );
+
+    let hashes = fields.iter().map(|field| {
+        let value = field.get_comparable_value_ref(quote!(self));
+        quote!(::std::hash::Hash::hash(#value, state);)
+    });
+
+    quote! {
+        impl #impl_generics ::std::cmp::Eq for #mutable_name #ty_generics #eq_where_clause {}
+
+        impl #impl_generics ::std::hash::Hash for #mutable_name #ty_generics #hash_where_clause {
+            fn hash<__H: ::std::hash::Hasher>(&self, state: &mut __H) {
+                #(#hashes)*
+            }
+        }
+    }
+}
+
+/// Adds `try_update`, which checks a snapshot with the struct's `#[signal_validate]`
+/// function before updating any field.
+fn impl_try_update(
+    input: &ItemStruct,
+    generics: &Generics,
+    mutable_name: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let traits = traits_crate();
+    let validate_fn = match maybe_get_validate_fn(input)? {
+        Some(validate_fn) => validate_fn,
+        None => return Ok(quote!()),
+    };
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+
+    Ok(quote! {
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            /// Updates every field like `update`, unless the `signal_validate` function
+            /// rejects the new snapshot, in which case no field is changed.
+            #original_vis fn try_update(
+                &self,
+                new_snapshot: #original_ident #ty_generics,
+            ) -> ::std::result::Result<(), #traits::ValidationError> {
+                #validate_fn(&new_snapshot)?;
+                #traits::MutableStruct::update(self, new_snapshot);
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// Implements `Keyed` for the original struct if it names its key field with
+/// `#[signal_key = "field"]`, so it can be an element of a `MutableKeyedVec`.
+fn impl_keyed(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+) -> syn::Result<proc_macro2::TokenStream> {
+    let traits = traits_crate();
+    let key_name = match maybe_get_string_attribute(&input.attrs, "signal_key", "id")? {
+        Some(key_name) => key_name,
+        None => return Ok(quote!()),
+    };
+    let key_field = fields
+        .iter()
+        .find(|field| match field.get_source_name() {
+            Member::Named(ident) => ident == &key_name.value(),
+            Member::Unnamed(index) => index.index.to_string() == key_name.value(),
+        })
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                &key_name,
+                format!("Found no field named {}.", key_name.value()),
+            )
+        })?;
+    let source_name = key_field.get_source_name();
+    let key_type = key_field.get_type();
+    let original_ident = &input.ident;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+
+    Ok(quote! {
+        impl #impl_generics #traits::keyed::Keyed
+            for #original_ident #ty_generics #where_clause
+        {
+            type Key = #key_type;
+
+            fn key(&self) -> #key_type {
+                ::std::clone::Clone::clone(&self.#source_name)
+            }
+        }
+    })
+}
+
+/// Implements `Deref` from the mutable struct to the nested mutable struct of the field
+/// annotated with `#[signal_flatten]`, so that the nested fields can be reached as if they
+/// belonged to the parent. Only one field can be flattened, since a type can only deref to
+/// one target. Newtypes annotated with `#[signal_transparent]` deref to the mutable type of
+/// their only field instead, whatever kind of field it is.
+fn impl_flatten(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut flattened = input
+        .fields
+        .iter()
+        .zip(fields)
+        .filter(|(field, _)| field_has_flag(field, "signal_flatten"));
+    let transparent = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("signal_transparent"));
+    let field = match (transparent, flattened.next()) {
+        (Some(attr), _) if fields.len() != 1 => {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "signal_transparent can only be used on structs with a single field.",
+            ));
+        }
+        (Some(_), Some((source_field, _))) => {
+            return Err(syn::Error::new_spanned(
+                source_field,
+                "signal_flatten cannot be combined with signal_transparent.",
+            ));
+        }
+        (Some(_), None) => &fields[0],
+        (None, Some((source_field, field))) => {
+            if let Some((extra_field, _)) = flattened.next() {
+                return Err(syn::Error::new_spanned(
+                    extra_field,
+                    "Only one field can be annotated with signal_flatten.",
+                ));
+            }
+            if !matches!(field, MutableStructField::MutableStruct { .. }) {
+                return Err(syn::Error::new_spanned(
+                    source_field,
+                    "signal_flatten can only be used on nested struct fields.",
+                ));
+            }
+            field
+        }
+        (None, None) => return Ok(quote!()),
+    };
+
+    let name = field.get_name();
+    let mutable_type = field.get_mutable_type();
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    Ok(quote! {
+        impl #impl_generics ::std::ops::Deref for #mutable_name #ty_generics #where_clause {
+            type Target = #mutable_type;
+
+            fn deref(&self) -> &Self::Target {
+                &self.#name
+            }
+        }
+    })
+}
+
+/// Implements `Default` for the mutable struct by converting the default snapshot. The impl
+/// is only available when the original struct implements `Default`.
+fn impl_default(
+    input: &ItemStruct,
+    generics: &Generics,
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let original_ident = &input.ident;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            std::iter::once(quote!(#original_ident #ty_generics)),
+            quote!(::std::default::Default),
+        ),
+    );
+
+    quote! {
+        impl #impl_generics ::std::default::Default for #mutable_name #ty_generics #where_clause {
+            fn default() -> Self {
+                #traits::AsMutableStruct::as_mutable_struct(
+                    &<#original_ident #ty_generics as ::std::default::Default>::default(),
+                )
+            }
+        }
+    }
+}
+
+/// Implements `PartialUpdate` for the mutable struct, setting only the fields that changed.
+/// The impl is only available when every updated field can be compared.
+fn impl_partial_update(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let original_ident = &input.ident;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(
+        generics,
+        make_predicates(
+            fields
+                .iter()
+                .filter_map(MutableStructField::get_changed_bound),
+        ),
+    );
+    let changed_setters = fields
+        .iter()
+        .filter_map(|field| field.get_changed_setter(format_ident!("new_snapshot")));
+    let version_bump = make_version_bump(input);
+
+    quote! {
+        impl #impl_generics #traits::PartialUpdate
+            for #mutable_name #ty_generics #where_clause
+        {
+            fn update_changed(&self, new_snapshot: #original_ident #ty_generics) {
+                #(#changed_setters;)*
+                #version_bump
+            }
+        }
+    }
+}
+
+/// Generates a `<MutableName>Builder` with a setter for each field. `build` fills in any
+/// field that was never set with its `Default` value, so it is only available when every
+/// field type implements `Default`.
+fn make_builder(
+    input: &ItemStruct,
+    generics: &Generics,
+    fields: &[MutableStructField],
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let builder_name = format_ident!("{}Builder", mutable_name);
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let build_bounds = make_bounds(
+        fields.iter().map(|field| {
+            let ty = field.get_type();
+            quote!(#ty)
+        }),
+        quote!(::std::default::Default),
+    );
+
+    let builder_fields = fields.iter().map(|field| {
+        let binding = field.get_binding();
+        let ty = field.get_type();
+        quote!(#binding: ::std::option::Option<#ty>)
+    });
+    let empty_fields = fields.iter().map(|field| {
+        let binding = field.get_binding();
+        quote!(#binding: ::std::option::Option::None)
+    });
+    let setters = fields.iter().map(|field| {
+        let binding = field.get_binding();
+        let vis = field.get_vis();
+        let ty = field.get_type();
+        quote! {
+            #vis fn #binding(mut self, value: #ty) -> Self {
+                self.#binding = ::std::option::Option::Some(value);
+                self
+            }
+        }
+    });
+    let snapshot = make_snapshot_literal(original_ident, fields, |field| {
+        let binding = field.get_binding();
+        quote!(self.#binding.unwrap_or_default())
+    });
+
+    quote! {
+        #[allow(non_snake_case)]
+        #original_vis struct #builder_name #generics #where_clause {
+            #(#builder_fields),*
+        }
+
+        impl #impl_generics ::std::default::Default for #builder_name #ty_generics #where_clause {
+            fn default() -> Self {
+                #builder_name {
+                    #(#empty_fields),*
+                }
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl #impl_generics #builder_name #ty_generics #where_clause {
+            #original_vis fn new() -> Self {
+                ::std::default::Default::default()
+            }
+
+            #(#setters)*
+
+            #original_vis fn build(self) -> #mutable_name #ty_generics where #build_bounds {
+                #traits::AsMutableStruct::as_mutable_struct(&#snapshot)
+            }
+        }
+    }
+}
+
+/// Implements `From` in both directions between the original struct and its mutable
+/// version, by way of `as_mutable_struct` and `snapshot`.
+fn impl_from(
+    input: &ItemStruct,
+    generics: &Generics,
+    mutable_name: &Ident,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let original_ident = &input.ident;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+
+    quote! {
+        impl #impl_generics ::std::convert::From<#original_ident #ty_generics>
+            for #mutable_name #ty_generics #where_clause
+        {
+            fn from(snapshot: #original_ident #ty_generics) -> Self {
+                #traits::AsMutableStruct::as_mutable_struct(&snapshot)
+            }
+        }
+
+        impl #impl_generics ::std::convert::From<&#mutable_name #ty_generics>
+            for #original_ident #ty_generics #where_clause
+        {
+            fn from(mutable: &#mutable_name #ty_generics) -> Self {
+                #traits::MutableStruct::snapshot(mutable)
+            }
+        }
+    }
+}
+
+/// Implements `Serialize` and `Deserialize` for the mutable struct when it is annotated with
+/// `#[signal_serde]`. Both go through a snapshot, so they are only available when the
+/// original struct implements them too.
+#[cfg(feature = "serde")]
+fn impl_serde(
+    input: &ItemStruct,
+    generics: &Generics,
+    mutable_name: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let traits = traits_crate();
+    if !has_flag(&input.attrs, "signal_serde") {
+        return Ok(quote!());
+    }
+
+    let original_ident = &input.ident;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let serialize_where_clause = make_where_clause(
+        generics,
+        make_bounds(
+            std::iter::once(quote!(#original_ident #ty_generics)),
+            quote!(#traits::serde::Serialize),
+        ),
+    );
+
+    let mut deserialize_generics = generics.clone();
+    deserialize_generics
+        .params
+        .insert(0, syn::parse_quote!('__de));
+    let (deserialize_impl_generics, _, _) = deserialize_generics.split_for_impl();
+    let deserialize_where_clause = make_where_clause(
+        generics,
+        quote!(#original_ident #ty_generics: #traits::serde::Deserialize<'__de>,),
+    );
+
+    Ok(quote! {
+        impl #impl_generics #traits::serde::Serialize
+            for #mutable_name #ty_generics #serialize_where_clause
+        {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: #traits::serde::Serializer,
+            {
+                #traits::serde::Serialize::serialize(
+                    &#traits::MutableStruct::snapshot(self),
+                    serializer,
+                )
+            }
+        }
+
+        impl #deserialize_impl_generics #traits::serde::Deserialize<'__de>
+            for #mutable_name #ty_generics #deserialize_where_clause
+        {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: #traits::serde::Deserializer<'__de>,
+            {
+                <#original_ident #ty_generics as #traits::serde::Deserialize<'__de>>::deserialize(
+                    deserializer,
+                )
+                .map(|snapshot| #traits::AsMutableStruct::as_mutable_struct(&snapshot))
+            }
+        }
+    })
+}
+
+#[cfg(not(feature = "serde"))]
+fn impl_serde(
+    input: &ItemStruct,
+    _generics: &Generics,
+    _mutable_name: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    match input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("signal_serde"))
+    {
+        Some(attr) => Err(syn::Error::new_spanned(
+            attr,
+            "signal_serde requires the serde feature of futures-signals-structs.",
+        )),
+        None => Ok(quote!()),
+    }
+}
+
+/// Adds `snapshot_json` when the struct is annotated with `#[signal_json]`, which converts a
+/// snapshot to a `serde_json::Value`. It is only available when the original struct
+/// implements `Serialize`.
+#[cfg(feature = "json")]
+fn impl_snapshot_json(
+    input: &ItemStruct,
+    generics: &Generics,
+    mutable_name: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let traits = traits_crate();
+    if !has_flag(&input.attrs, "signal_json") {
+        return Ok(quote!());
+    }
+
+    let original_ident = &input.ident;
+    let original_vis = &input.vis;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
+    let serialize_bounds = make_bounds(
+        std::iter::once(quote!(#original_ident #ty_generics)),
+        quote!(#traits::serde::Serialize),
+    );
+
+    Ok(quote! {
+        impl #impl_generics #mutable_name #ty_generics #where_clause {
+            /// Returns a snapshot converted to JSON, for logging and debugging. Panics if the
+            /// snapshot can't be represented as JSON, such as a map with non-string keys.
+            #original_vis fn snapshot_json(&self) -> #traits::serde_json::Value
+            where
+                #serialize_bounds
+            {
+                #traits::serde_json::to_value(#traits::MutableStruct::snapshot(self))
+                    .expect("snapshot can be converted to JSON")
+            }
+        }
+    })
+}
+
+#[cfg(not(feature = "json"))]
+fn impl_snapshot_json(
+    input: &ItemStruct,
+    _generics: &Generics,
+    _mutable_name: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    match input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("signal_json"))
+    {
+        Some(attr) => Err(syn::Error::new_spanned(
+            attr,
+            "signal_json requires the json feature of futures-signals-structs.",
+        )),
+        None => Ok(quote!()),
+    }
+}
+
+/// Asserts that every basic field is `Clone`, with the error pointing at the field, since
+/// the generated code clones them in many places that would otherwise each report it at the
+/// derive. Fields whose type uses a generic parameter can't be named outside the impls, and
+/// are bounded on `Clone` in their where clauses instead. Skipped fields don't need to be
+/// `Clone`, see `add_skipped_clone_bounds`.
+fn make_clone_assertions(
+    generics: &Generics,
+    fields: &[MutableStructField],
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let params = generics
+        .lifetimes()
+        .map(|param| param.lifetime.ident.clone())
+        .chain(generics.type_params().map(|param| param.ident.clone()))
+        .chain(generics.const_params().map(|param| param.ident.clone()))
+        .collect::<Vec<Ident>>();
+    let assertions = fields
+        .iter()
+        .filter(|field| matches!(field, MutableStructField::Basic { .. }))
+        .map(MutableStructField::get_type)
+        .filter(|ty| !tokens_mention(ty.to_token_stream(), &params))
+        .map(|ty| quote_spanned!(ty.span()=> assert_clone_field::<#ty>();))
+        .collect::<Vec<proc_macro2::TokenStream>>();
+    if assertions.is_empty() {
+        return quote!();
+    }
+
+    quote! {
+        const _: fn() = || {
+            fn assert_clone_field<T: #traits::CloneField>() {}
+            #(#assertions)*
+        };
+    }
+}
+
+/// Asserts that the `#[mutable_type]` of each nested field is a `MutableStruct` whose
+/// snapshots are the field's type, spanned at the named type, so that a wrong type gets a
+/// clear error there. Vectors of mutable structs go through `MutableStructElements` instead,
+/// and types that mention the struct's generic parameters are left to the generated impls.
+fn make_mutable_type_assertions(
+    generics: &Generics,
+    fields: &[MutableStructField],
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let params = generics
+        .lifetimes()
+        .map(|param| param.lifetime.ident.clone())
+        .chain(generics.type_params().map(|param| param.ident.clone()))
+        .chain(generics.const_params().map(|param| param.ident.clone()))
+        .collect::<Vec<Ident>>();
+    let assertions = fields
+        .iter()
+        .filter_map(|field| match field {
+            MutableStructField::MutableStruct {
+                ty,
+                mutable_type: Some(mutable_type),
+                element_type: None,
+                ..
+            } => Some((ty, mutable_type)),
+            _ => None,
+        })
+        .filter(|(ty, _)| !tokens_mention(ty.to_token_stream(), &params))
+        .map(|(ty, mutable_type)| {
+            quote_spanned!(mutable_type.span()=> assert_mutable_type::<#ty, #mutable_type>();)
+        })
+        .collect::<Vec<proc_macro2::TokenStream>>();
+    if assertions.is_empty() {
+        return quote!();
+    }
+
+    quote! {
+        const _: fn() = || {
+            fn assert_mutable_type<T, M: #traits::MutableTypeField<T>>() {}
+            #(#assertions)*
+        };
+    }
+}
+
+/// Asserts that the mutable struct is `Send` and `Sync` when the struct is annotated with
+/// `#[signal_assert_thread_safe]`, so a field that breaks this is reported at the struct
+/// rather than wherever the mutable struct is first sent to another thread.
+fn make_thread_safe_assertion(
+    input: &ItemStruct,
+    mutable_name: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let attr = match input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("signal_assert_thread_safe"))
+    {
+        Some(attr) => attr,
+        None => return Ok(quote!()),
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "signal_assert_thread_safe is not supported on generic structs.",
+        ));
+    }
+
+    let original_ident = &input.ident;
+    Ok(quote_spanned! {original_ident.span()=>
+        const _: fn() = || {
+            fn assert_send_sync<T: ::std::marker::Send + ::std::marker::Sync>() {}
+            assert_send_sync::<#mutable_name>();
+        };
+    })
+}
 
 fn impl_as_signal_struct(
     input: ItemStruct,
-    fields: &Vec<MutableStructField>,
+    generics: &Generics,
+    fields: &[MutableStructField],
     mutable_name: &Ident,
 ) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    let version_init = make_version_init(&input);
     let ident = input.ident;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = make_where_clause(generics, quote!());
 
     let mutable_fields = fields
         .iter()
@@ -253,39 +4202,920 @@ fn impl_as_signal_struct(
         .collect::<Vec<proc_macro2::TokenStream>>();
 
     quote! {
-        impl futures_signals_structs_traits::AsMutableStruct for #ident {
-            type MutableStructType = #mutable_name;
+        impl #impl_generics #traits::AsMutableStruct
+            for #ident #ty_generics #where_clause
+        {
+            type MutableStructType = #mutable_name #ty_generics;
 
-            fn as_mutable_struct(&self) -> #mutable_name {
+            fn as_mutable_struct(&self) -> #mutable_name #ty_generics {
                 #mutable_name {
-                    #(#mutable_fields),*
+                    #(#mutable_fields,)*
+                    #version_init
                 }
             }
         }
     }
 }
 
-fn maybe_get_mutable_name(input: ItemStruct) -> Option<String> {
-    for attr in input.attrs {
-        if let syn::AttrStyle::Inner(_) = attr.style {
-            continue;
+/// Builds the `Owned<Name>` twin of a struct with reference fields annotated with
+/// `#[signal_store_as]`, in which each of them has the owned type named by the attribute
+/// instead, along with the code that declares the twin, implements `Clone`, `Debug` and
+/// `PartialEq` for it where its fields do, and converts the original struct into it.
+/// Returns None if no field needs it. Lifetime parameters that only the references use are
+/// dropped from the twin.
+fn make_owned_twin(
+    input: &ItemStruct,
+) -> syn::Result<Option<(ItemStruct, proc_macro2::TokenStream)>> {
+    let is_stored_reference = |field: &Field| -> syn::Result<bool> {
+        Ok(matches!(field.ty, Type::Reference(_))
+            && !field_has_flag(field, "signal_skip")
+            && maybe_get_store_as(field)?.is_some())
+    };
+    let mut has_stored_reference = false;
+    for field in input.fields.iter() {
+        has_stored_reference |= is_stored_reference(field)?;
+    }
+    if !has_stored_reference {
+        return Ok(None);
+    }
+
+    let original_ident = &input.ident;
+    let twin_ident = format_ident!("Owned{}", original_ident);
+    let mut twin = input.clone();
+    twin.ident = twin_ident.clone();
+    let mut converters = vec![];
+    let mut conversion_bounds = vec![];
+    for (index, field) in twin.fields.iter_mut().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        };
+        if is_stored_reference(field)? {
+            let reference_ty = field.ty.clone();
+            let owned_ty = maybe_get_store_as(field)?.unwrap();
+            field
+                .attrs
+                .retain(|attr| !attr.path.is_ident("signal_store_as"));
+            field.ty = *owned_ty;
+            let owned_ty = &field.ty;
+            converters.push(quote!(#member: ::std::convert::Into::into(value.#member)));
+            conversion_bounds.push(quote!(#reference_ty: ::std::convert::Into<#owned_ty>));
+        } else {
+            converters.push(quote!(#member: value.#member));
+        }
+    }
+
+    // Drops the lifetimes that no remaining field uses, along with the bounds that mention
+    // them.
+    let used_lifetimes = twin
+        .generics
+        .lifetimes()
+        .map(|param| param.lifetime.ident.clone())
+        .filter(|lifetime| {
+            twin.fields.iter().any(|field| {
+                tokens_mention(field.ty.to_token_stream(), std::slice::from_ref(lifetime))
+            })
+        })
+        .collect::<Vec<Ident>>();
+    let dropped_lifetimes = twin
+        .generics
+        .lifetimes()
+        .map(|param| param.lifetime.ident.clone())
+        .filter(|lifetime| !used_lifetimes.contains(lifetime))
+        .collect::<Vec<Ident>>();
+    twin.generics.params = twin
+        .generics
+        .params
+        .into_iter()
+        .filter(|param| match param {
+            syn::GenericParam::Lifetime(param) => {
+                !dropped_lifetimes.contains(&param.lifetime.ident)
+            }
+            _ => true,
+        })
+        .collect();
+    if let Some(where_clause) = &mut twin.generics.where_clause {
+        where_clause.predicates = where_clause
+            .predicates
+            .clone()
+            .into_iter()
+            .filter(|predicate| !tokens_mention(predicate.to_token_stream(), &dropped_lifetimes))
+            .collect();
+    }
+
+    // The mutable struct keeps the name it would have had without the twin.
+    if maybe_get_mutable_name(input)?.is_none() {
+        let mutable_name =
+            LitStr::new(&format!("Mutable{}", original_ident), original_ident.span());
+        twin.attrs
+            .push(syn::parse_quote!(#[MutableStructName = #mutable_name]));
+    }
+
+    // The twin is declared without the helper attributes, which only this derive accepts.
+    let mut declaration = twin.clone();
+    declaration.attrs.retain(|attr| attr.path.is_ident("doc"));
+    for field in declaration.fields.iter_mut() {
+        field.attrs.retain(|attr| attr.path.is_ident("doc"));
+    }
+    let twin_doc = format!(
+        "An owned copy of [`{}`], which is the snapshot type of its mutable struct.",
+        original_ident
+    );
+
+    let (impl_generics, ty_generics, _) = twin.generics.split_for_impl();
+    let field_types = twin
+        .fields
+        .iter()
+        .map(|field| field.ty.to_token_stream())
+        .collect::<Vec<proc_macro2::TokenStream>>();
+    let members = twin
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        })
+        .collect::<Vec<Member>>();
+    let clone_where_clause = make_where_clause(
+        &twin.generics,
+        make_bounds(field_types.iter().cloned(), quote!(::std::clone::Clone)),
+    );
+    let debug_where_clause = make_where_clause(
+        &twin.generics,
+        make_bounds(field_types.iter().cloned(), quote!(::std::fmt::Debug)),
+    );
+    let partial_eq_where_clause = make_where_clause(
+        &twin.generics,
+        make_bounds(field_types.iter().cloned(), quote!(::std::cmp::PartialEq)),
+    );
+    let twin_ident_str = twin_ident.to_string();
+    let debug_fields = members.iter().map(|member| match member {
+        Member::Named(ident) => {
+            let name = ident.to_string();
+            quote!(.field(#name, &self.#member))
+        }
+        Member::Unnamed(_) => quote!(.field(&self.#member)),
+    });
+    let debug_builder = match &twin.fields {
+        Fields::Named(_) => quote!(debug_struct),
+        _ => quote!(debug_tuple),
+    };
+    // Twins always have a field, since they are only built for structs with references.
+    let comparisons = members
+        .iter()
+        .map(|member| quote!(self.#member == other.#member))
+        .collect::<Vec<proc_macro2::TokenStream>>();
+    let (first_comparison, other_comparisons) = comparisons.split_first().unwrap();
+
+    let (original_impl_generics, original_ty_generics, _) = input.generics.split_for_impl();
+    let from_where_clause = make_where_clause(
+        &input.generics,
+        make_predicates(conversion_bounds.into_iter()),
+    );
+
+    let items = quote! {
+        #[doc = #twin_doc]
+        #declaration
+
+        impl #impl_generics ::std::clone::Clone for #twin_ident #ty_generics #clone_where_clause {
+            fn clone(&self) -> Self {
+                #twin_ident {
+                    #(#members: ::std::clone::Clone::clone(&self.#members)),*
+                }
+            }
+        }
+
+        impl #impl_generics ::std::fmt::Debug for #twin_ident #ty_generics #debug_where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.#debug_builder(#twin_ident_str)
+                    #(#debug_fields)*
+                    .finish()
+            }
+        }
+
+        impl #impl_generics ::std::cmp::PartialEq for #twin_ident #ty_generics #partial_eq_where_clause {
+            fn eq(&self, other: &Self) -> bool {
+                #first_comparison #(&& #other_comparisons)*
+            }
+        }
+
+        impl #original_impl_generics ::std::convert::From<#original_ident #original_ty_generics>
+            for #twin_ident #ty_generics #from_where_clause
+        {
+            fn from(value: #original_ident #original_ty_generics) -> Self {
+                #twin_ident {
+                    #(#converters),*
+                }
+            }
+        }
+    };
+    Ok(Some((twin, items)))
+}
+
+/// Creates a MutableStructField for every field of the struct. Fields annotated with
+/// `#[signal_snapshot_flatten = "name: Type"]` are gathered into a single nested field called
+/// `name`, in place of the first of them, whose struct has a field of the same name for each.
+fn parse_fields(input: &ItemStruct, accessor_suffix: &str) -> syn::Result<Vec<MutableStructField>> {
+    let mut fields: Vec<MutableStructField> = vec![];
+    for (index, field) in input.fields.iter().enumerate() {
+        let (nested_name, nested_ty) = match maybe_get_snapshot_flatten(field)? {
+            Some(nested_field) => nested_field,
+            None => {
+                fields.push(MutableStructField::new(index, field, accessor_suffix)?);
+                continue;
+            }
+        };
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "signal_snapshot_flatten can only be used on named fields.",
+                ))
+            }
+        };
+        if let Some(attr) = field.attrs.iter().find(|attr| {
+            let name = attr.path.to_token_stream().to_string();
+            (name.starts_with("signal_") || name.starts_with("mutable"))
+                && name != "signal_snapshot_flatten"
+        }) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "signal_snapshot_flatten cannot be combined with other field attributes.",
+            ));
         }
-        if !attr.path.is_ident("MutableStructName") {
+
+        let nested_member = Member::Named(nested_name.clone());
+        let existing = fields
+            .iter_mut()
+            .find(|field| field.get_flattened().is_some() && field.get_name() == &nested_member);
+        if let Some(existing) = existing {
+            if existing.get_type().to_token_stream().to_string()
+                != nested_ty.to_token_stream().to_string()
+            {
+                return Err(syn::Error::new_spanned(
+                    &nested_ty,
+                    format!(
+                        "Every field flattened into {} has to name the same type.",
+                        nested_name
+                    ),
+                ));
+            }
+            if let MutableStructField::MutableStruct { flattened, .. } = existing {
+                flattened.push(member);
+            }
             continue;
         }
-        if let Result::Ok(parsed_meta) = attr.parse_meta() {
-            if let syn::Meta::NameValue(name_value) = parsed_meta {
-                if let syn::Lit::Str(lit_str) = name_value.lit {
-                    return Some(lit_str.value());
-                } else {
-                    panic!("Found a MutableStructName that is not a string.")
+
+        let nested_field = Field {
+            attrs: vec![],
+            vis: field.vis.clone(),
+            ident: Some(nested_name),
+            colon_token: Some(Default::default()),
+            ty: nested_ty.clone(),
+        };
+        let mut nested = MutableStructField::new(index, &nested_field, accessor_suffix)?;
+        match &mut nested {
+            MutableStructField::MutableStruct {
+                element_type: None,
+                flattened,
+                ..
+            } => flattened.push(member),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &nested_ty,
+                    "signal_snapshot_flatten can only gather fields into a nested struct.",
+                ))
+            }
+        }
+        fields.push(nested);
+    }
+    Ok(fields)
+}
+
+/// Adds the bounds that the fields of a generic struct need in order to be converted to
+/// and from their mutable versions. Only fields that mention a type parameter need them.
+fn add_field_bounds(generics: &Generics, fields: &[MutableStructField]) -> Generics {
+    let mut generics = generics.clone();
+    let type_params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<Ident>>();
+    if type_params.is_empty() {
+        return generics;
+    }
+
+    let where_clause = generics.make_where_clause();
+    for field in fields {
+        if tokens_mention(field.get_type().to_token_stream(), &type_params) {
+            where_clause.predicates.extend(field.get_generic_bounds());
+        }
+    }
+    generics
+}
+
+/// Adds a `Clone` bound for every skipped field whose type doesn't already get one from
+/// `add_field_bounds`. Snapshots clone skipped fields, so the impls that take them are only
+/// available when the bound holds, while the mutable struct, its constructor and its
+/// accessors still work for skipped fields that can't be cloned, such as boxed closures.
+fn add_skipped_clone_bounds(generics: &Generics, fields: &[MutableStructField]) -> Generics {
+    let mut generics = generics.clone();
+    let type_params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<Ident>>();
+    let where_clause = generics.make_where_clause();
+    for field in fields {
+        let ty = field.get_type();
+        if matches!(field, MutableStructField::Skipped { .. })
+            && !tokens_mention(ty.to_token_stream(), &type_params)
+        {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(for<'__field> #ty: ::std::clone::Clone));
+        }
+    }
+    generics
+}
+
+/// Removes the invisible groups that `macro_rules!` wraps around `$ty:ty` fragments, which
+/// would otherwise hide `Option<u32>` or `Vec<T>` from the checks that look at type paths.
+/// Groups around trait objects become parentheses, so `Box<$ty>` keeps its meaning.
+fn ungroup_type(ty: &mut Type) {
+    struct Ungroup;
+
+    impl syn::visit_mut::VisitMut for Ungroup {
+        fn visit_type_mut(&mut self, ty: &mut Type) {
+            while let Type::Group(group) = ty {
+                let elem = (*group.elem).clone();
+                *ty = match elem {
+                    Type::TraitObject(_) | Type::ImplTrait(_) => Type::Paren(syn::TypeParen {
+                        paren_token: Default::default(),
+                        elem: Box::new(elem),
+                    }),
+                    elem => elem,
+                };
+            }
+            syn::visit_mut::visit_type_mut(self, ty);
+        }
+    }
+
+    syn::visit_mut::VisitMut::visit_type_mut(&mut Ungroup, ty);
+}
+
+/// Returns the hidden field of the mutable struct that holds its `StructVersion`, if the
+/// struct is annotated with `#[signal_versioned]`. Tuple structs keep it after their own
+/// fields, and unit structs get it as a named field.
+fn get_version_member(input: &ItemStruct) -> Option<Member> {
+    if !has_flag(&input.attrs, "signal_versioned") {
+        return None;
+    }
+    Some(match input.fields {
+        Fields::Unnamed(_) => Member::Unnamed(input.fields.len().into()),
+        _ => Member::Named(format_ident!("__signal_version")),
+    })
+}
+
+/// Returns the initializer of the hidden version field, followed by a comma, for struct
+/// literals of the mutable struct.
+fn make_version_init(input: &ItemStruct) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    match get_version_member(input) {
+        Some(member) => quote!(#member: #traits::version::StructVersion::new(),),
+        None => quote!(),
+    }
+}
+
+/// Returns a statement that increases the version of the struct on `self`, if it keeps one.
+fn make_version_bump(input: &ItemStruct) -> proc_macro2::TokenStream {
+    match get_version_member(input) {
+        Some(member) => quote!(self.#member.bump();),
+        None => quote!(),
+    }
+}
+
+/// Wraps `signal` so that it is held back while a batch is running, if the struct keeps a
+/// `version`.
+fn hold_signal(
+    version: Option<&proc_macro2::TokenStream>,
+    signal: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let traits = traits_crate();
+    match version {
+        Some(version) => quote!(#traits::version::StructVersion::hold(&#version, #signal)),
+        None => signal,
+    }
+}
+
+/// Returns true if any identifier in `tokens` is one of `idents`.
+fn tokens_mention(tokens: proc_macro2::TokenStream, idents: &[Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => idents.contains(&ident),
+        proc_macro2::TokenTree::Group(group) => tokens_mention(group.stream(), idents),
+        _ => false,
+    })
+}
+
+/// Builds a non-mutable version of the struct out of the code that `value` returns for each
+/// field. Values of flattened fields are bound to a variable first, so that they can be
+/// spread over the fields of the original struct they were gathered from.
+fn make_snapshot_literal(
+    original_ident: &Ident,
+    fields: &[MutableStructField],
+    value: impl Fn(&MutableStructField) -> proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut bindings = vec![];
+    let mut initializers = vec![];
+    for field in fields {
+        let value = value(field);
+        if field.get_flattened().is_some() {
+            let binding = field.get_binding();
+            bindings.push(quote!(let #binding = #value;));
+            initializers.push(field.get_snapshot_initializers(quote!(#binding)));
+        } else {
+            initializers.push(field.get_snapshot_initializers(value));
+        }
+    }
+    let literal = quote!(#original_ident { #(#initializers),* });
+    if bindings.is_empty() {
+        literal
+    } else {
+        quote!({
+            #(#bindings)*
+            #literal
+        })
+    }
+}
+
+/// Returns a struct type as a path that can start a struct literal, which needs `::`
+/// before its generic arguments.
+fn type_as_expr_path(ty: &Type) -> proc_macro2::TokenStream {
+    match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => {
+            let mut path = type_path.path.clone();
+            for segment in path.segments.iter_mut() {
+                if let syn::PathArguments::AngleBracketed(arguments) = &mut segment.arguments {
+                    arguments.colon2_token = Some(Default::default());
                 }
+            }
+            path.to_token_stream()
+        }
+        _ => ty.to_token_stream(),
+    }
+}
+
+/// Builds a where clause out of the struct's own where clause plus `bounds`.
+fn make_where_clause(
+    generics: &Generics,
+    bounds: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|where_clause| where_clause.predicates.iter());
+    quote!(where #(#predicates,)* #bounds)
+}
+
+/// Builds predicates requiring every type in `types` to implement `bound`. Bounds on
+/// concrete types that don't hold are compile errors, so they are written as
+/// higher-ranked bounds, which instead just leave the impl unavailable.
+fn make_bounds(
+    types: impl Iterator<Item = proc_macro2::TokenStream>,
+    bound: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    make_predicates(types.map(|ty| quote!(#ty: #bound)))
+}
+
+/// Returns a `PhantomData` type that uses every generic parameter, or None if there are
+/// none.
+fn make_phantom_type(generics: &Generics) -> Option<proc_macro2::TokenStream> {
+    let phantom_types = generics
+        .lifetimes()
+        .map(|lifetime| {
+            let lifetime = &lifetime.lifetime;
+            quote!(&#lifetime ())
+        })
+        .chain(generics.type_params().map(|param| {
+            let ident = &param.ident;
+            quote!(#ident)
+        }))
+        .collect::<Vec<proc_macro2::TokenStream>>();
+    if phantom_types.is_empty() {
+        None
+    } else {
+        Some(quote!(::std::marker::PhantomData<(#(#phantom_types,)*)>))
+    }
+}
+
+/// Returns a hidden, uninhabited enum variant that uses every generic parameter, since an
+/// enum has to use them all even when none of its variants need them.
+fn make_phantom_variant(generics: &Generics) -> proc_macro2::TokenStream {
+    match make_phantom_type(generics) {
+        Some(phantom_type) => quote! {
+            #[doc(hidden)]
+            __Unused(#phantom_type, ::std::convert::Infallible),
+        },
+        None => quote!(),
+    }
+}
+
+/// Builds predicates out of `Type: Bound` pairs, made higher-ranked for the same reason as
+/// in `make_bounds`.
+fn make_predicates(
+    predicates: impl Iterator<Item = proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    let predicates = predicates.map(|predicate| quote!(for<'__field> #predicate));
+    quote!(#(#predicates,)*)
+}
+
+fn maybe_get_mutable_name(input: &ItemStruct) -> syn::Result<Option<String>> {
+    let outer_attrs = input
+        .attrs
+        .iter()
+        .filter(|attr| matches!(attr.style, syn::AttrStyle::Outer))
+        .cloned()
+        .collect::<Vec<Attribute>>();
+    let grouped_name = maybe_get_grouped_string(input, "name", "MyMutableName")?;
+    match maybe_get_string_attribute(&outer_attrs, "MutableStructName", "MyMutableName")? {
+        Some(lit_str) if grouped_name.is_some() => Err(syn::Error::new_spanned(
+            lit_str,
+            "MutableStructName cannot be combined with #[mutable(name)].",
+        )),
+        Some(lit_str) => Ok(Some(lit_str.value())),
+        None => Ok(grouped_name.map(|lit_str| lit_str.value())),
+    }
+}
+
+fn maybe_get_mutable_type(field: &Field) -> syn::Result<Option<Box<Type>>> {
+    maybe_get_string_attribute(&field.attrs, "mutable_type", "MyMutableType")?
+        .map(|lit_str| {
+            lit_str.parse().map(Box::new).map_err(|_| {
+                syn::Error::new_spanned(lit_str, "Found a mutable_type that is not a valid type.")
+            })
+        })
+        .transpose()
+}
+
+fn maybe_get_rename(field: &Field) -> syn::Result<Option<Ident>> {
+    maybe_get_string_attribute(&field.attrs, "signal_rename", "new_name")?
+        .map(|lit_str| {
+            lit_str.parse().map_err(|_| {
+                syn::Error::new_spanned(
+                    lit_str,
+                    "Found a signal_rename that is not a valid identifier.",
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Finds a field attribute formatted as `#[signal_snapshot_flatten = "name: Type"]` and
+/// parses the name and type of the nested field it is gathered into.
+fn maybe_get_snapshot_flatten(field: &Field) -> syn::Result<Option<(Ident, Type)>> {
+    let parse_nested_field = |input: syn::parse::ParseStream| {
+        let name = input.parse::<Ident>()?;
+        input.parse::<syn::Token![:]>()?;
+        let ty = input.parse::<Type>()?;
+        Ok((name, ty))
+    };
+    maybe_get_string_attribute(&field.attrs, "signal_snapshot_flatten", "name: Type")?
+        .map(|lit_str| {
+            lit_str.parse_with(parse_nested_field).map_err(|_| {
+                syn::Error::new_spanned(
+                    lit_str,
+                    "Found a signal_snapshot_flatten that is not formatted as \"name: Type\".",
+                )
+            })
+        })
+        .transpose()
+}
+
+fn maybe_get_signal_accessor(field: &Field) -> syn::Result<Option<Ident>> {
+    maybe_get_string_attribute(&field.attrs, "signal_accessor", "observe_field")?
+        .map(|lit_str| {
+            lit_str.parse().map_err(|_| {
+                syn::Error::new_spanned(
+                    lit_str,
+                    "Found a signal_accessor that is not a valid identifier.",
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Returns the traits listed in `#[mutable_derive(...)]`, which are derived for the mutable
+/// struct.
+fn maybe_get_mutable_derives(input: &ItemStruct) -> syn::Result<Vec<syn::Path>> {
+    let mut derives = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("mutable_derive"))
+    {
+        let paths = attr
+            .parse_args_with(
+                syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated,
+            )
+            .map_err(|_| {
+                syn::Error::new_spanned(
+                    attr,
+                    "Format mutable_derive as #[mutable_derive(Debug, Clone)]",
+                )
+            })?;
+        derives.extend(paths);
+    }
+    for meta in get_grouped_options(input, "derive")? {
+        let paths = match meta {
+            syn::Meta::List(list) => list
+                .nested
+                .into_iter()
+                .map(|nested| match nested {
+                    syn::NestedMeta::Meta(syn::Meta::Path(path)) => Ok(path),
+                    nested => Err(syn::Error::new_spanned(
+                        nested,
+                        "Format the derive option as #[mutable(derive(Debug, Clone))]",
+                    )),
+                })
+                .collect::<syn::Result<Vec<syn::Path>>>()?,
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "Format the derive option as #[mutable(derive(Debug, Clone))]",
+                ))
+            }
+        };
+        derives.extend(paths);
+    }
+    Ok(derives)
+}
+
+fn maybe_get_mutable_vis(input: &ItemStruct) -> syn::Result<Option<Visibility>> {
+    let grouped_vis = maybe_get_grouped_string(input, "vis", "pub(crate)")?;
+    let lit_str = match maybe_get_string_attribute(&input.attrs, "mutable_vis", "pub(crate)")? {
+        Some(lit_str) if grouped_vis.is_some() => {
+            return Err(syn::Error::new_spanned(
+                lit_str,
+                "mutable_vis cannot be combined with #[mutable(vis)].",
+            ))
+        }
+        Some(lit_str) => Some(lit_str),
+        None => grouped_vis,
+    };
+    lit_str.map(|lit_str| lit_str.parse()).transpose()
+}
+
+/// Returns the paths of the traits crate and of futures-signals, which default to
+/// `::futures_signals_structs_traits` and `::futures_signals` and can be changed with
+/// `#[signal_crate(traits = "path", signals = "path")]`.
+fn get_crate_paths(input: &ItemStruct) -> syn::Result<(syn::Path, syn::Path)> {
+    let (mut traits, mut signals) = default_crate_paths();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("signal_crate"))
+    {
+        let format_error = || {
+            syn::Error::new_spanned(
+                attr,
+                "Format signal_crate as #[signal_crate(traits = \"my_traits\", signals = \"my_signals\")]",
+            )
+        };
+        let nested = match attr.parse_meta().map_err(|_| format_error())? {
+            syn::Meta::List(list) => list.nested,
+            _ => return Err(format_error()),
+        };
+        for nested_meta in nested {
+            let (path, lit_str) = match nested_meta {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit_str),
+                    ..
+                })) => (path, lit_str),
+                _ => return Err(format_error()),
+            };
+            let crate_path = lit_str.parse::<syn::Path>().map_err(|_| {
+                syn::Error::new_spanned(&lit_str, "Found a signal_crate that is not a valid path.")
+            })?;
+            if path.is_ident("traits") {
+                traits = crate_path;
+            } else if path.is_ident("signals") {
+                signals = crate_path;
             } else {
-                panic!("Format MutableStructName as #[MutableStructName = \"MyMutableName\"]")
+                return Err(syn::Error::new_spanned(
+                    path,
+                    "Unknown signal_crate option. Expected traits or signals.",
+                ));
             }
-        } else {
-            panic!("Found a malformed MutableStructName. Format MutableStructName as #[MutableStructName = \"Name\"]");
         }
     }
-    return Option::None;
+    Ok((traits, signals))
+}
+
+/// The options that can be grouped into a `#[mutable(...)]` attribute on the struct, as an
+/// alternative to `MutableStructName`, `mutable_vis` and `mutable_derive`.
+const GROUPED_OPTIONS: &[&str] = &["name", "vis", "derive"];
+
+/// Returns every option named `key` in the struct's `#[mutable(...)]` attributes, such as
+/// `#[mutable(name = "MyMutableName", vis = "pub", derive(Debug))]`. Fails on options that
+/// aren't in `GROUPED_OPTIONS`.
+fn get_grouped_options(input: &ItemStruct, key: &str) -> syn::Result<Vec<syn::Meta>> {
+    let mut options = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("mutable"))
+    {
+        let format_error = || {
+            syn::Error::new_spanned(
+                attr,
+                "Format mutable as #[mutable(name = \"MyMutableName\", vis = \"pub\", derive(Debug))]",
+            )
+        };
+        let nested = match attr.parse_meta().map_err(|_| format_error())? {
+            syn::Meta::List(list) => list.nested,
+            _ => return Err(format_error()),
+        };
+        for nested_meta in nested {
+            let meta = match nested_meta {
+                syn::NestedMeta::Meta(meta) => meta,
+                syn::NestedMeta::Lit(_) => return Err(format_error()),
+            };
+            if !GROUPED_OPTIONS
+                .iter()
+                .any(|option| meta.path().is_ident(option))
+            {
+                return Err(syn::Error::new_spanned(
+                    meta.path(),
+                    "Unknown mutable option. Expected name, vis or derive.",
+                ));
+            }
+            if meta.path().is_ident(key) {
+                options.push(meta);
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Returns the string value of the option named `key` in the struct's `#[mutable(...)]`
+/// attributes, if it is set.
+fn maybe_get_grouped_string(
+    input: &ItemStruct,
+    key: &str,
+    example: &str,
+) -> syn::Result<Option<LitStr>> {
+    match get_grouped_options(input, key)?.pop() {
+        Some(syn::Meta::NameValue(syn::MetaNameValue {
+            lit: syn::Lit::Str(lit_str),
+            ..
+        })) => Ok(Some(lit_str)),
+        Some(meta) => Err(syn::Error::new_spanned(
+            meta,
+            format!(
+                "Format the {} option as #[mutable({} = \"{}\")]",
+                key, key, example
+            ),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Returns the suffix appended to field names to name their signal accessors, which is
+/// `_signal` unless the struct sets `#[signal_accessor_suffix]`.
+fn maybe_get_accessor_suffix(input: &ItemStruct) -> syn::Result<String> {
+    let lit_str =
+        match maybe_get_string_attribute(&input.attrs, "signal_accessor_suffix", "_changes")? {
+            Some(lit_str) => lit_str,
+            None => return Ok("_signal".to_string()),
+        };
+    let suffix = lit_str.value();
+    if syn::parse_str::<Ident>(&format!("field{}", suffix)).is_err() {
+        return Err(syn::Error::new_spanned(
+            lit_str,
+            "Found a signal_accessor_suffix that can not be part of an identifier.",
+        ));
+    }
+    Ok(suffix)
+}
+
+fn maybe_get_eq_fn(field: &Field) -> syn::Result<Option<Box<syn::Path>>> {
+    maybe_get_string_attribute(&field.attrs, "signal_eq_fn", "path::to::eq_fn")?
+        .map(|lit_str| {
+            lit_str.parse().map(Box::new).map_err(|_| {
+                syn::Error::new_spanned(lit_str, "Found a signal_eq_fn that is not a valid path.")
+            })
+        })
+        .transpose()
+}
+
+fn maybe_get_validate_fn(input: &ItemStruct) -> syn::Result<Option<Box<syn::Path>>> {
+    maybe_get_string_attribute(&input.attrs, "signal_validate", "path::to::validate")?
+        .map(|lit_str| {
+            lit_str.parse().map(Box::new).map_err(|_| {
+                syn::Error::new_spanned(
+                    lit_str,
+                    "Found a signal_validate that is not a valid path.",
+                )
+            })
+        })
+        .transpose()
+}
+
+fn maybe_get_store_as(field: &Field) -> syn::Result<Option<Box<Type>>> {
+    maybe_get_string_attribute(&field.attrs, "signal_store_as", "String")?
+        .map(|lit_str| {
+            lit_str.parse().map(Box::new).map_err(|_| {
+                syn::Error::new_spanned(
+                    lit_str,
+                    "Found a signal_store_as that is not a valid type.",
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Finds a field attribute formatted as `#[name = "100"]` and parses its value as the
+/// expression that values of the field are clamped to.
+fn maybe_get_limit(field: &Field, name: &str) -> syn::Result<Option<Box<syn::Expr>>> {
+    maybe_get_string_attribute(&field.attrs, name, "100")?
+        .map(|lit_str| {
+            lit_str.parse().map(Box::new).map_err(|_| {
+                syn::Error::new_spanned(
+                    &lit_str,
+                    format!("Found a {} that is not a valid expression.", name),
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Finds a field attribute formatted as `#[name = "path::to::fn"]` and parses its value.
+fn maybe_get_function(field: &Field, name: &str) -> syn::Result<Option<Box<syn::Path>>> {
+    maybe_get_string_attribute(&field.attrs, name, "path::to::fn")?
+        .map(|lit_str| {
+            lit_str.parse().map(Box::new).map_err(|_| {
+                syn::Error::new_spanned(
+                    &lit_str,
+                    format!("Found a {} that is not a valid path.", name),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn maybe_get_wrapper(field: &Field) -> syn::Result<Option<Box<syn::Path>>> {
+    maybe_get_string_attribute(&field.attrs, "signal_wrap", "Arc")?
+        .map(|lit_str| {
+            lit_str.parse().map(Box::new).map_err(|_| {
+                syn::Error::new_spanned(lit_str, "Found a signal_wrap that is not a valid path.")
+            })
+        })
+        .transpose()
+}
+
+/// Finds an attribute formatted as `#[name = "value"]` and returns its value. `example`
+/// is used to show the expected format in error messages.
+fn maybe_get_string_attribute(
+    attrs: &[Attribute],
+    name: &str,
+    example: &str,
+) -> syn::Result<Option<LitStr>> {
+    for attr in attrs {
+        if !attr.path.is_ident(name) {
+            continue;
+        }
+        let parsed_meta = attr.parse_meta().map_err(|_| {
+            syn::Error::new_spanned(
+                attr,
+                format!(
+                    "Found a malformed {}. Format {} as #[{} = \"{}\"]",
+                    name, name, name, example
+                ),
+            )
+        })?;
+        return match parsed_meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(lit_str),
+                ..
+            }) => Ok(Some(lit_str)),
+            syn::Meta::NameValue(name_value) => Err(syn::Error::new_spanned(
+                name_value.lit,
+                format!("Found a {} that is not a string.", name),
+            )),
+            meta => Err(syn::Error::new_spanned(
+                meta,
+                format!("Format {} as #[{} = \"{}\"]", name, name, example),
+            )),
+        };
+    }
+    Ok(Option::None)
+}
+
+fn field_has_flag(field: &Field, flag: &str) -> bool {
+    has_flag(&field.attrs, flag)
+}
+
+fn has_flag(attrs: &[Attribute], flag: &str) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident(flag))
 }
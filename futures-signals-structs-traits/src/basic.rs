@@ -0,0 +1,120 @@
+use crate::{ApplyDiff, AsMutableStruct, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::{Mutable, Signal};
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Basic values are stored in a single `Mutable`. Derived structs do this for their basic
+/// fields directly, so these impls are mostly useful for type parameters of generic
+/// structs, which may be instantiated with either a basic value or a nested struct.
+impl<T: Clone + 'static> MutableStruct for Mutable<T> {
+    type SnapshotType = T;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        self.get_cloned()
+    }
+
+    fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+        out.clone_from(&*self.lock_ref());
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        Mutable::new(snapshot.clone())
+    }
+
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        self.set(new_snapshot);
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        self.signal_cloned()
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> PartialUpdate for Mutable<T> {
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        self.set_neq(new_snapshot);
+    }
+}
+
+/// A single value has no fields of its own, so its diff contains one `()` if it changed.
+impl<T: Clone + PartialEq + 'static> Diff for Mutable<T> {
+    type Field = ();
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        if *self.lock_ref() == *other {
+            vec![]
+        } else {
+            vec![()]
+        }
+    }
+}
+
+impl<T: Clone + 'static> ApplyDiff for Mutable<T> {
+    type Patch = T;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.set(patch);
+    }
+}
+
+macro_rules! impl_basic_as_mutable_struct {
+    ($($ty:ty),*) => {
+        $(
+            impl AsMutableStruct for $ty {
+                type MutableStructType = Mutable<$ty>;
+
+                fn as_mutable_struct(&self) -> Self::MutableStructType {
+                    Mutable::new(self.clone())
+                }
+            }
+        )*
+    };
+}
+
+impl_basic_as_mutable_struct!(
+    bool,
+    char,
+    f32,
+    f64,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    String,
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
+    NonZeroU128,
+    NonZeroUsize,
+    NonZeroI8,
+    NonZeroI16,
+    NonZeroI32,
+    NonZeroI64,
+    NonZeroI128,
+    NonZeroIsize,
+    Duration,
+    Instant,
+    SystemTime
+);
+
+/// A `Result` is an opaque value, so changing between `Ok` and `Err` or changing the value
+/// inside either replaces the whole `Result`.
+impl<T: Clone + 'static, E: Clone + 'static> AsMutableStruct for Result<T, E> {
+    type MutableStructType = Mutable<Result<T, E>>;
+
+    fn as_mutable_struct(&self) -> Self::MutableStructType {
+        Mutable::new(self.clone())
+    }
+}
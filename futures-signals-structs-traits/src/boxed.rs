@@ -0,0 +1,70 @@
+use crate::{ApplyDiff, AsMutableStruct, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::{Signal, SignalExt};
+
+/// A boxed struct becomes a boxed mutable struct. The nested mutable struct is built from
+/// the value inside the box, and every snapshot allocates a new box around its snapshot.
+impl<T: AsMutableStruct + 'static> AsMutableStruct for Box<T> {
+    type MutableStructType = Box<T::MutableStructType>;
+
+    fn as_mutable_struct(&self) -> Self::MutableStructType {
+        Box::new((**self).as_mutable_struct())
+    }
+}
+
+impl<M: MutableStruct> MutableStruct for Box<M>
+where
+    M::SnapshotType: 'static,
+{
+    type SnapshotType = Box<M::SnapshotType>;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        Box::new((**self).snapshot())
+    }
+
+    fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+        (**self).snapshot_into(out);
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        Box::new(M::from_snapshot(snapshot))
+    }
+
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        (**self).update(*new_snapshot);
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        (**self).snapshot_signal().map(Box::new)
+    }
+}
+
+impl<M: PartialUpdate> PartialUpdate for Box<M>
+where
+    M::SnapshotType: 'static,
+{
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        (**self).update_changed(*new_snapshot);
+    }
+}
+
+impl<M: Diff> Diff for Box<M>
+where
+    M::SnapshotType: 'static,
+{
+    type Field = M::Field;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        (**self).diff(other)
+    }
+}
+
+impl<M: ApplyDiff> ApplyDiff for Box<M>
+where
+    M::SnapshotType: 'static,
+{
+    type Patch = M::Patch;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        (**self).apply_diff(patch);
+    }
+}
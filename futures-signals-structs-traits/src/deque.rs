@@ -0,0 +1,123 @@
+use crate::{ApplyDiff, AsMutableStruct, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::Signal;
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Deref;
+
+impl<T: Clone + 'static> AsMutableStruct for VecDeque<T> {
+    type MutableStructType = MutableVecDeque<T>;
+
+    fn as_mutable_struct(&self) -> Self::MutableStructType {
+        MutableVecDeque::new_with_values(self.clone())
+    }
+}
+
+/// Mutable version of a `VecDeque`, backed by a `MutableVec` since futures-signals has no
+/// deque type. The front of the deque is index 0 of the vector and the back is its last
+/// index, so `signal_vec_cloned` and snapshots list elements from front to back. Derefs to
+/// the underlying `MutableVec`.
+///
+/// Pushing to the front shifts every other element, like inserting at the start of a `Vec`.
+pub struct MutableVecDeque<T>(MutableVec<T>);
+
+impl<T: Clone> MutableVecDeque<T> {
+    pub fn new_with_values(values: VecDeque<T>) -> Self {
+        MutableVecDeque(MutableVec::new_with_values(values.into()))
+    }
+
+    pub fn push_front(&self, value: T) {
+        self.0.lock_mut().insert_cloned(0, value);
+    }
+
+    pub fn push_back(&self, value: T) {
+        self.0.lock_mut().push_cloned(value);
+    }
+
+    pub fn pop_front(&self) -> Option<T> {
+        let mut lock = self.0.lock_mut();
+        if lock.is_empty() {
+            None
+        } else {
+            Some(lock.remove(0))
+        }
+    }
+
+    pub fn pop_back(&self) -> Option<T> {
+        self.0.lock_mut().pop()
+    }
+}
+
+impl<T> Deref for MutableVecDeque<T> {
+    type Target = MutableVec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MutableVecDeque<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MutableVecDeque")
+            .field(&self.0.lock_ref().as_slice())
+            .finish()
+    }
+}
+
+impl<T: Clone + 'static> MutableStruct for MutableVecDeque<T> {
+    type SnapshotType = VecDeque<T>;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        self.0.lock_ref().iter().cloned().collect()
+    }
+
+    fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+        out.clear();
+        out.extend(self.0.lock_ref().iter().cloned());
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableVecDeque::new_with_values(snapshot.clone())
+    }
+
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        self.0.lock_mut().replace_cloned(new_snapshot.into());
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        self.0
+            .signal_vec_cloned()
+            .to_signal_map(|values| values.iter().cloned().collect())
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> PartialUpdate for MutableVecDeque<T> {
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        let mut lock = self.0.lock_mut();
+        if !lock.iter().eq(new_snapshot.iter()) {
+            lock.replace_cloned(new_snapshot.into());
+        }
+    }
+}
+
+/// Deques report the indices of elements that differ, counting from the front, including
+/// every index past the end of the shorter deque.
+impl<T: Clone + PartialEq + 'static> Diff for MutableVecDeque<T> {
+    type Field = usize;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        let lock = self.0.lock_ref();
+        let max_len = lock.len().max(other.len());
+        (0..max_len)
+            .filter(|index| lock.get(*index) != other.get(*index))
+            .collect()
+    }
+}
+
+impl<T: Clone + 'static> ApplyDiff for MutableVecDeque<T> {
+    type Patch = VecDeque<T>;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.update(patch);
+    }
+}
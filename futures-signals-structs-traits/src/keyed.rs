@@ -0,0 +1,173 @@
+use crate::{ApplyDiff, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::Signal;
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Implemented by snapshots that carry a key identifying them within a `MutableKeyedVec`,
+/// such as the ID of an entity. Deriving `AsMutableStruct` on a struct annotated with
+/// `#[signal_key = "field"]` implements it using that field.
+pub trait Keyed {
+    type Key: Eq + Hash + Clone;
+
+    /// Returns the key of this snapshot.
+    fn key(&self) -> Self::Key;
+}
+
+/// Mutable version of a `Vec` of structs that are identified by a key, like
+/// `MutableStructVec`, but which matches elements to snapshots by key rather than by
+/// position. Updating it with a reordered snapshot moves the existing mutable elements, so
+/// subscribers to an element keep following the same entity. Use it by annotating a
+/// `Vec<T>` field with `#[mutable_type = "MutableKeyedVec<MutableT>"]`, where `T`
+/// implements `Keyed`.
+///
+/// Elements are stored in an `Arc`, and the vector derefs to the underlying `MutableVec`.
+pub struct MutableKeyedVec<M>(MutableVec<Arc<M>>);
+
+impl<M> MutableKeyedVec<M> {
+    pub fn new_with_values(values: Vec<M>) -> Self {
+        MutableKeyedVec(MutableVec::new_with_values(
+            values.into_iter().map(Arc::new).collect(),
+        ))
+    }
+}
+
+impl<M> Deref for MutableKeyedVec<M> {
+    type Target = MutableVec<Arc<M>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for MutableKeyedVec<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MutableKeyedVec")
+            .field(&self.0.lock_ref().as_slice())
+            .finish()
+    }
+}
+
+impl<M: MutableStruct + 'static> MutableKeyedVec<M>
+where
+    M::SnapshotType: Keyed + Clone,
+{
+    /// Matches every snapshot to the existing element with the same key, passing both to
+    /// `update_element`, and creates new elements for snapshots without one. Elements whose
+    /// key is no longer present are removed. The vector itself only changes if elements
+    /// were added, removed or reordered.
+    fn reconcile<F>(&self, new_snapshot: Vec<M::SnapshotType>, update_element: F)
+    where
+        F: Fn(&M, M::SnapshotType),
+    {
+        let mut lock = self.0.lock_mut();
+        // Keys are read from snapshots, since the key field of an element may have been
+        // changed directly.
+        let mut existing = lock
+            .iter()
+            .map(|element| (M::snapshot(element).key(), Arc::clone(element)))
+            .collect::<HashMap<_, _>>();
+        let elements = new_snapshot
+            .into_iter()
+            .map(|new_value| match existing.remove(&new_value.key()) {
+                Some(element) => {
+                    update_element(&element, new_value);
+                    element
+                }
+                None => Arc::new(M::from_snapshot(&new_value)),
+            })
+            .collect::<Vec<Arc<M>>>();
+
+        let unchanged = lock.len() == elements.len()
+            && lock
+                .iter()
+                .zip(&elements)
+                .all(|(current, new)| Arc::ptr_eq(current, new));
+        if !unchanged {
+            lock.replace_cloned(elements);
+        }
+    }
+}
+
+impl<M: MutableStruct + 'static> MutableStruct for MutableKeyedVec<M>
+where
+    M::SnapshotType: Keyed + Clone,
+{
+    type SnapshotType = Vec<M::SnapshotType>;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        self.0
+            .lock_ref()
+            .iter()
+            .map(|element| M::snapshot(element))
+            .collect()
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableKeyedVec::new_with_values(snapshot.iter().map(M::from_snapshot).collect())
+    }
+
+    /// Updates the elements whose key is still present in place, moving them to their new
+    /// position, and creates or removes elements for keys that were added or removed.
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        self.reconcile(new_snapshot, |element, new_value| {
+            M::update(element, new_value)
+        });
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        // Element signals borrow from the element, so they are boxed to give them a
+        // 'static type.
+        self.0
+            .signal_vec_cloned()
+            .map_signal(|element| {
+                Box::pin(M::snapshot_signal(&element))
+                    as Pin<Box<dyn Signal<Item = M::SnapshotType>>>
+            })
+            .to_signal_cloned()
+    }
+}
+
+impl<M: PartialUpdate + 'static> PartialUpdate for MutableKeyedVec<M>
+where
+    M::SnapshotType: Keyed + Clone,
+{
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        self.reconcile(new_snapshot, |element, new_value| {
+            M::update_changed(element, new_value)
+        });
+    }
+}
+
+impl<M: Diff + 'static> Diff for MutableKeyedVec<M>
+where
+    M::SnapshotType: Keyed + Clone,
+{
+    type Field = usize;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        let lock = self.0.lock_ref();
+        let max_len = lock.len().max(other.len());
+        (0..max_len)
+            .filter(|index| match (lock.get(*index), other.get(*index)) {
+                (Some(element), Some(other_element)) => !M::diff(element, other_element).is_empty(),
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+impl<M: MutableStruct + 'static> ApplyDiff for MutableKeyedVec<M>
+where
+    M::SnapshotType: Keyed + Clone,
+{
+    type Patch = Vec<M::SnapshotType>;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.update(patch);
+    }
+}
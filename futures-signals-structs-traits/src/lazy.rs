@@ -0,0 +1,152 @@
+use crate::{ApplyDiff, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::Signal;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// A nested mutable struct that is only built the first time it is accessed. Until then it
+/// holds the snapshot it was created from, which `snapshot` and `update` read and replace
+/// directly, while `diff` and `apply_diff` build a temporary mutable struct from it that is
+/// dropped right away. The derive stores fields annotated with `#[signal_lazy]` this way, to
+/// avoid building large trees of mutable structs that are never observed.
+///
+/// Derefs to the nested mutable struct, building it if necessary. Subscribing to its
+/// `snapshot_signal` builds it as well.
+pub struct MutableLazy<M: MutableStruct> {
+    value: OnceLock<M>,
+    pending: Mutex<Option<M::SnapshotType>>,
+}
+
+impl<M: MutableStruct> MutableLazy<M> {
+    pub fn new(snapshot: M::SnapshotType) -> Self {
+        MutableLazy {
+            value: OnceLock::new(),
+            pending: Mutex::new(Some(snapshot)),
+        }
+    }
+
+    /// Returns the nested mutable struct, building it from the pending snapshot if this is
+    /// the first access.
+    pub fn get(&self) -> &M {
+        self.value.get_or_init(|| {
+            let snapshot = self
+                .pending
+                .lock()
+                .unwrap()
+                .take()
+                .expect("a MutableLazy without a value has a pending snapshot");
+            M::from_snapshot(&snapshot)
+        })
+    }
+
+    /// Returns true once the nested mutable struct has been built.
+    pub fn is_initialized(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Locks the pending snapshot if the nested struct hasn't been built yet, which keeps
+    /// it from being built until the guard is dropped. Returns None once it has been built,
+    /// or while a concurrent first access is building it, in which case `get` waits for it.
+    fn lock_pending(&self) -> Option<MutexGuard<'_, Option<M::SnapshotType>>> {
+        if self.is_initialized() {
+            return None;
+        }
+        Some(self.pending.lock().unwrap()).filter(|pending| pending.is_some())
+    }
+}
+
+impl<M: MutableStruct> Deref for MutableLazy<M> {
+    type Target = M;
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
+impl<M: MutableStruct + fmt::Debug> fmt::Debug for MutableLazy<M>
+where
+    M::SnapshotType: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some(value) => f.debug_tuple("MutableLazy").field(value).finish(),
+            None => f
+                .debug_tuple("MutableLazy")
+                .field(&*self.pending.lock().unwrap())
+                .finish(),
+        }
+    }
+}
+
+impl<M: MutableStruct> MutableStruct for MutableLazy<M>
+where
+    M::SnapshotType: Clone,
+{
+    type SnapshotType = M::SnapshotType;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        match self.lock_pending() {
+            Some(pending) => Option::clone(&pending).unwrap(),
+            None => self.get().snapshot(),
+        }
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableLazy::new(snapshot.clone())
+    }
+
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        match self.lock_pending() {
+            Some(mut pending) => *pending = Some(new_snapshot),
+            None => self.get().update(new_snapshot),
+        }
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        self.get().snapshot_signal()
+    }
+}
+
+impl<M: PartialUpdate> PartialUpdate for MutableLazy<M>
+where
+    M::SnapshotType: Clone,
+{
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        match self.lock_pending() {
+            Some(mut pending) => *pending = Some(new_snapshot),
+            None => self.get().update_changed(new_snapshot),
+        }
+    }
+}
+
+impl<M: Diff> Diff for MutableLazy<M>
+where
+    M::SnapshotType: Clone,
+{
+    type Field = M::Field;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        match self.lock_pending() {
+            Some(pending) => M::from_snapshot(Option::as_ref(&pending).unwrap()).diff(other),
+            None => self.get().diff(other),
+        }
+    }
+}
+
+impl<M: ApplyDiff> ApplyDiff for MutableLazy<M>
+where
+    M::SnapshotType: Clone,
+{
+    type Patch = M::Patch;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        match self.lock_pending() {
+            Some(mut pending) => {
+                let patched = M::from_snapshot(Option::as_ref(&pending).unwrap());
+                patched.apply_diff(patch);
+                *pending = Some(patched.snapshot());
+            }
+            None => self.get().apply_diff(patch),
+        }
+    }
+}
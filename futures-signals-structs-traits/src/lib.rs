@@ -1,22 +1,388 @@
+pub mod basic;
+pub mod boxed;
+pub mod deque;
+pub mod keyed;
+pub mod lazy;
+pub mod map;
+pub mod option;
+pub mod poll;
+pub mod set;
+pub mod shared;
+pub mod synced;
 pub mod vec;
+pub mod version;
+pub mod watch;
+
+use futures_core::Stream;
+use futures_signals::signal::{Signal, SignalExt};
+use poll::SnapshotPoller;
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::sync::Arc;
+use synced::SyncedMutex;
+use version::StructVersion;
+
+#[cfg(feature = "serde")]
+pub use serde;
+#[cfg(feature = "json")]
+pub use serde_json;
 
 pub trait MutableStruct {
     type SnapshotType;
 
     /// Returns a non-mutable version of this struct, which is a basic Rust struct
     /// that can be passed around to code that is not aware of futures-signals.
-    /// 
+    ///
     /// Note that 'non-mutable' in this context does not mean immutable in the Rust
     /// sense. It just means that the struct is not a MutableStruct and therefore
     /// changes are not tracked by futures-signals.
+    ///
+    /// Fields are read one at a time, so a snapshot taken while another thread is updating
+    /// the struct may contain a mix of old and new values.
+    #[must_use]
     fn snapshot(&self) -> Self::SnapshotType;
 
+    /// Writes a snapshot into `out`, reusing its existing allocations where possible, such
+    /// as the buffers of strings and vectors. This avoids allocating a new snapshot for
+    /// every frame when the same struct is read in a loop.
+    fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+        *out = self.snapshot();
+    }
+
+    /// Creates a new MutableStruct from a non-mutable struct. For most types this is the
+    /// same as calling `as_mutable_struct()` on the snapshot, but it also allows building
+    /// alternative mutable representations of the same type.
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self;
+
     /// Updates every field in this MutableStruct to match an non-mutable struct.
     fn update(&self, new_snapshot: Self::SnapshotType);
+
+    /// Updates every field to match a borrowed non-mutable struct, like `update`, so the
+    /// caller can keep using the snapshot. Derived structs only clone the values of their
+    /// basic fields, while other types clone the whole snapshot.
+    fn update_ref(&self, new_snapshot: &Self::SnapshotType)
+    where
+        Self::SnapshotType: Clone,
+    {
+        self.update(new_snapshot.clone());
+    }
+
+    /// Sets every field to the current value of the same field on `other`, like `update`
+    /// with a snapshot of `other`. Derived structs read the values directly instead of taking
+    /// a snapshot first, and recurse into nested structs.
+    fn merge(&self, other: &Self) {
+        self.update(other.snapshot());
+    }
+
+    /// Updates every field to match a non-mutable struct, like `update`, and returns a
+    /// snapshot of the values that were replaced.
+    #[must_use = "use update to replace the fields without returning the previous values"]
+    fn replace_with(&self, new_snapshot: Self::SnapshotType) -> Self::SnapshotType {
+        let previous = self.snapshot();
+        self.update(new_snapshot);
+        previous
+    }
+
+    /// Restores every field to the value it has in the default non-mutable struct. Nested
+    /// structs are updated in place, so subscribers to their fields are notified too.
+    fn reset(&self)
+    where
+        Self::SnapshotType: Default,
+    {
+        self.update(Self::SnapshotType::default());
+    }
+
+    /// Returns a future that updates this struct with every snapshot produced by `signal`,
+    /// and completes when the signal ends. The future takes ownership of the struct, so it
+    /// is only `'static` (and so can be spawned) when the struct is. To keep observing the
+    /// struct, subscribe to its signals beforehand or keep clones of its field `Mutable`s,
+    /// which share their values. Cloning the struct itself creates an independent copy.
+    fn update_from_signal<S>(self, signal: S) -> impl Future<Output = ()>
+    where
+        Self: Sized,
+        S: Signal<Item = Self::SnapshotType>,
+    {
+        signal.for_each(move |snapshot| {
+            self.update(snapshot);
+            std::future::ready(())
+        })
+    }
+
+    /// Moves this struct into an `Arc`, so it can be shared across tasks and threads. Every
+    /// clone of the `Arc` shares the same underlying `Mutable`s, unlike cloning the struct.
+    fn into_shared(self) -> Arc<Self>
+    where
+        Self: Sized,
+    {
+        Arc::new(self)
+    }
+
+    /// Moves this struct into a future that keeps it in sync with a mutex-guarded snapshot,
+    /// for code that locks a shared value rather than using signals. Field changes are
+    /// copied into the mutex, and writes through the mutex update the struct, whenever the
+    /// future is polled. The future never completes, so it should be spawned.
+    ///
+    /// The snapshot is held in a `SyncedMutex` rather than a plain `std::sync::Mutex`,
+    /// whose guards can't tell anyone that they were written through. Without that, the
+    /// future would have to compare the snapshot with the struct on every poll to find
+    /// writes, and couldn't be woken up when one happens. `SyncedMutex::lock` returns a
+    /// guard that derefs to the snapshot like a `MutexGuard`, so most code that locks an
+    /// `Arc<Mutex<_>>` only needs its type changed.
+    fn into_synced_mutex(
+        self,
+    ) -> (
+        Arc<SyncedMutex<Self::SnapshotType>>,
+        impl Future<Output = ()>,
+    )
+    where
+        Self: Sized,
+        Self::SnapshotType: Clone + 'static,
+    {
+        synced::sync_with_mutex(self)
+    }
+
+    /// Returns a signal that produces a fresh snapshot of the whole struct whenever any of
+    /// its fields change. Like all signals, intermediate changes may be skipped if several
+    /// fields change before the signal is polled.
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static;
+
+    /// Returns a stream of snapshots, for consumers that expect a `Stream` rather than a
+    /// `Signal`. Like the snapshot signal, it starts with the current snapshot and may skip
+    /// intermediate changes.
+    fn snapshot_stream(&self) -> impl Stream<Item = Self::SnapshotType> + 'static {
+        self.snapshot_signal().to_stream()
+    }
+
+    /// Returns a poller for the snapshot signal, for reading changes from a synchronous loop
+    /// without an async executor. Each poll produces a fresh snapshot if any field changed
+    /// since the previous poll.
+    fn snapshot_poller(&self) -> SnapshotPoller<Self::SnapshotType>
+    where
+        Self::SnapshotType: 'static,
+    {
+        SnapshotPoller::new(self.snapshot_signal())
+    }
+
+    /// Like `snapshot_signal`, but skips snapshots that are equal to the previous one, such
+    /// as after a field was set to the value it already had.
+    fn snapshot_signal_dedupe(&self) -> impl Signal<Item = Self::SnapshotType> + 'static
+    where
+        Self::SnapshotType: PartialEq + Clone + 'static,
+    {
+        self.snapshot_signal().dedupe_cloned()
+    }
+
+    /// Returns a signal that projects every snapshot through `f`, for deriving computed
+    /// views of the struct that update whenever any of its fields change.
+    fn map_snapshot<U, F>(&self, mut f: F) -> impl Signal<Item = U> + 'static
+    where
+        F: FnMut(&Self::SnapshotType) -> U + 'static,
+    {
+        self.snapshot_signal().map(move |snapshot| f(&snapshot))
+    }
+
+    /// Returns a signal of snapshots of this struct and an independent `other` one, which
+    /// produces a new pair whenever either of them changes.
+    fn zip_snapshot_signal<B>(
+        &self,
+        other: &B,
+    ) -> impl Signal<Item = (Self::SnapshotType, B::SnapshotType)> + 'static
+    where
+        Self::SnapshotType: Clone + 'static,
+        B: MutableStruct,
+        B::SnapshotType: Clone + 'static,
+    {
+        futures_signals::map_ref! {
+            let own = self.snapshot_signal(),
+            let other = other.snapshot_signal() =>
+            (own.clone(), other.clone())
+        }
+    }
+
+    /// Returns a signal that is `true` whenever the struct differs from `baseline`, such as
+    /// the last saved version, for showing that there are unsaved changes. It only produces
+    /// a new value when that changes, and goes back to `false` once every field is reverted.
+    fn is_dirty_signal(&self, baseline: Self::SnapshotType) -> impl Signal<Item = bool> + 'static
+    where
+        Self::SnapshotType: PartialEq + 'static,
+    {
+        self.snapshot_signal()
+            .map(move |snapshot| snapshot != baseline)
+            .dedupe()
+    }
+
+    /// Returns a future that calls `callback` with the current snapshot, and again with a
+    /// new snapshot whenever any field changes. It completes once the struct is dropped.
+    fn observe<F>(&self, mut callback: F) -> impl Future<Output = ()> + 'static
+    where
+        F: FnMut(Self::SnapshotType) + 'static,
+    {
+        self.snapshot_signal().for_each(move |snapshot| {
+            callback(snapshot);
+            std::future::ready(())
+        })
+    }
+
+    /// Like `observe`, but also passes the snapshot the callback was previously called with,
+    /// which is `None` on the first call.
+    fn observe_changes<F>(&self, mut callback: F) -> impl Future<Output = ()> + 'static
+    where
+        Self::SnapshotType: Clone + 'static,
+        F: FnMut(Option<Self::SnapshotType>, Self::SnapshotType) + 'static,
+    {
+        let mut previous = None;
+        self.snapshot_signal().for_each(move |snapshot| {
+            callback(previous.replace(snapshot.clone()), snapshot);
+            std::future::ready(())
+        })
+    }
+}
+
+/// Implemented by MutableStructs that can compare their current values to a snapshot, which
+/// allows updating them without notifying subscribers of fields that did not change.
+pub trait PartialUpdate: MutableStruct {
+    /// Updates only the fields whose current value differs from the non-mutable struct,
+    /// recursing into nested structs. Fields that already match keep their signals quiet.
+    fn update_changed(&self, new_snapshot: Self::SnapshotType);
+}
+
+/// Implemented by MutableStructs that can report which of their fields differ from a
+/// snapshot.
+pub trait Diff: MutableStruct {
+    /// Names a field that changed. Nested structs name the field along with the changes
+    /// inside it, and collections name the elements that changed.
+    type Field: Debug + Clone + PartialEq;
+
+    /// Returns the fields whose current value differs from `other`, in declaration order.
+    #[must_use]
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field>;
+}
+
+/// Implemented by MutableStructs that can apply a patch holding new values for only some of
+/// their fields, such as one sent over the network.
+pub trait ApplyDiff: MutableStruct {
+    /// Holds new values for any of the fields. Derived structs use a generated struct with
+    /// an `Option` for each field, while other types are patched with a full snapshot.
+    type Patch;
+
+    /// Updates the fields that are present in `patch`, leaving all others untouched.
+    fn apply_diff(&self, patch: Self::Patch);
+}
+
+/// Implemented by mutable structs annotated with `#[signal_versioned]`, which keep a hidden
+/// version counter and can hold back their signals while several fields are changed.
+pub trait Versioned: MutableStruct {
+    /// Returns the hidden version state of the struct.
+    #[doc(hidden)]
+    fn struct_version(&self) -> &StructVersion;
+
+    /// Returns the current version of the struct. It starts at 0, and goes up by one with
+    /// every call to a generated setter, `update`, `update_changed`, `apply_diff` or another
+    /// method that sets several fields, and once for every `batch`, however its fields are
+    /// set. Outside of a batch, setting a field's `Mutable` directly, or changing a nested
+    /// struct through its own methods, doesn't change the version, so it only counts the
+    /// changes made through the methods of the mutable struct.
+    fn version(&self) -> u64 {
+        self.struct_version().get()
+    }
+
+    /// Returns a signal of the current version, for cheaply detecting that something
+    /// changed without taking snapshots. Every subscriber sees the same version.
+    fn version_signal(&self) -> impl Signal<Item = u64> + 'static {
+        self.struct_version().signal()
+    }
+
+    /// Runs `f`, which can set any number of fields, so that it counts as a single change to
+    /// the struct. The struct's snapshot signal and field signals don't produce any values
+    /// while the batch is running, even when polled on another thread, and then produce one
+    /// value with every change applied. Signals taken from a field's `Mutable` directly are
+    /// not held back.
+    fn batch<F>(&self, f: F)
+    where
+        F: FnOnce(&Self),
+    {
+        self.struct_version().batch(|| f(self))
+    }
+}
+
+/// Gives access to the signal of the field named by the marker type `F`. The derive
+/// generates a marker, such as `player_score_fields::Points`, for every field that is stored
+/// in the mutable struct, which lets generic code subscribe to fields without knowing the
+/// names of their accessors.
+pub trait GetFieldSignal<F> {
+    /// The snapshot type of the field.
+    type Value;
+
+    /// Returns a signal of the field's snapshot, which changes whenever the field does.
+    fn get_field_signal(&self, field: F) -> impl Signal<Item = Self::Value> + 'static;
+}
+
+/// Returned by the `signal_validate` function of a derived struct to reject a snapshot
+/// passed to `try_update`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    message: String,
+}
+
+impl ValidationError {
+    pub fn new(message: impl Into<String>) -> Self {
+        ValidationError {
+            message: message.into(),
+        }
+    }
+
+    /// Returns the message describing why the snapshot was rejected.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Implemented for every `Clone` type. The derive asserts it for each field that it clones
+/// into snapshots, so a field that can't be cloned is reported where it is declared.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `Clone` to be a field of a mutable struct",
+    label = "this field is cloned into every snapshot",
+    note = "basic and skipped fields are copied out of the mutable struct by cloning them"
+)]
+pub trait CloneField: Clone {}
+
+impl<T: Clone> CloneField for T {}
+
+/// Implemented by every `MutableStruct` for its snapshot type. The derive asserts it for each
+/// field with a `#[mutable_type]`, so a type that doesn't fit the field is reported where it
+/// is named.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be the mutable_type of a `{T}` field",
+    label = "this type must implement `MutableStruct<SnapshotType = {T}>`",
+    note = "the mutable_type of a field stores the field and produces its snapshots"
+)]
+pub trait MutableTypeField<T> {}
+
+impl<T, M: MutableStruct<SnapshotType = T>> MutableTypeField<T> for M {}
+
+/// Clamps a value to the `#[signal_min]` and `#[signal_max]` of its field, for the setters
+/// and updates generated by the derive.
+#[doc(hidden)]
+pub fn clamp_to_limits<T: PartialOrd>(value: T, min: Option<T>, max: Option<T>) -> T {
+    match (min, max) {
+        (Some(min), _) if value < min => min,
+        (_, Some(max)) if value > max => max,
+        _ => value,
+    }
 }
 
 pub trait AsMutableStruct {
-    type MutableStructType: MutableStruct;
+    type MutableStructType: MutableStruct<SnapshotType = Self>;
 
     /// Returns a Mutable version of this struct. Note that mutable in this context
     /// does not mean `mut` in the Rust sense. Instead it means that every field on
@@ -24,4 +390,4 @@ pub trait AsMutableStruct {
     /// futures-signals crate. This means that any changes to the struct can be
     /// tracked using signals.
     fn as_mutable_struct(&self) -> Self::MutableStructType;
-}
\ No newline at end of file
+}
@@ -0,0 +1,244 @@
+use crate::{ApplyDiff, AsMutableStruct, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::{Signal, SignalExt};
+use futures_signals::signal_map::MutableBTreeMap;
+use futures_signals::signal_vec::SignalVecExt;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+
+impl<K: Ord + Clone + 'static, V: Clone + 'static> AsMutableStruct for BTreeMap<K, V> {
+    type MutableStructType = MutableBTreeMap<K, V>;
+
+    fn as_mutable_struct(&self) -> Self::MutableStructType {
+        MutableBTreeMap::with_values(self.clone())
+    }
+}
+
+impl<K: Ord + Clone + 'static, V: Clone + 'static> MutableStruct for MutableBTreeMap<K, V> {
+    type SnapshotType = BTreeMap<K, V>;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        self.lock_ref().clone()
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableBTreeMap::with_values(snapshot.clone())
+    }
+
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        self.lock_mut().replace_cloned(new_snapshot);
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        self.entries_cloned()
+            .to_signal_map(|entries| entries.iter().cloned().collect())
+    }
+}
+
+impl<K, V> PartialUpdate for MutableBTreeMap<K, V>
+where
+    K: Ord + Clone + 'static,
+    V: Clone + PartialEq + 'static,
+{
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        let mut lock = self.lock_mut();
+        if *lock != new_snapshot {
+            lock.replace_cloned(new_snapshot);
+        }
+    }
+}
+
+/// Maps report the keys whose values differ, including keys that only one side contains.
+impl<K, V> Diff for MutableBTreeMap<K, V>
+where
+    K: Ord + Clone + Debug + 'static,
+    V: Clone + PartialEq + 'static,
+{
+    type Field = K;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        let lock = self.lock_ref();
+        let mut keys = lock.keys().chain(other.keys()).collect::<Vec<&K>>();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter(|key| lock.get(*key) != other.get(*key))
+            .cloned()
+            .collect()
+    }
+}
+
+impl<K: Ord + Clone + 'static, V: Clone + 'static> ApplyDiff for MutableBTreeMap<K, V> {
+    type Patch = BTreeMap<K, V>;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.update(patch);
+    }
+}
+
+/// Mutable version of a `BTreeMap` whose values are structs, like `MutableBTreeMap`, but
+/// which stores a mutable struct for each value so that the fields of individual entries
+/// can be observed. Updating it with a new snapshot updates the entries whose key is still
+/// present in place, so their subscribers keep following them. Use it by annotating a
+/// `BTreeMap<K, T>` field with `#[mutable_type = "MutableStructMap<K, MutableT>"]`.
+///
+/// Values are stored in an `Arc`, and the map derefs to the underlying `MutableBTreeMap`.
+pub struct MutableStructMap<K, M>(MutableBTreeMap<K, Arc<M>>);
+
+impl<K, M> MutableStructMap<K, M> {
+    pub fn new_with_values(values: BTreeMap<K, M>) -> Self
+    where
+        K: Ord,
+    {
+        MutableStructMap(MutableBTreeMap::with_values(
+            values
+                .into_iter()
+                .map(|(key, value)| (key, Arc::new(value)))
+                .collect(),
+        ))
+    }
+}
+
+impl<K, M> Deref for MutableStructMap<K, M> {
+    type Target = MutableBTreeMap<K, Arc<M>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K: Debug, M: Debug> fmt::Debug for MutableStructMap<K, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MutableStructMap")
+            .field(&*self.0.lock_ref())
+            .finish()
+    }
+}
+
+impl<K, M> MutableStructMap<K, M>
+where
+    K: Ord + Clone + 'static,
+    M: MutableStruct + 'static,
+{
+    /// Passes every snapshot to `update_entry` along with the existing entry for its key,
+    /// and creates entries for keys that are new. Entries whose key is no longer present
+    /// are removed. The map itself only changes if entries were added or removed.
+    fn reconcile<F>(&self, new_snapshot: BTreeMap<K, M::SnapshotType>, update_entry: F)
+    where
+        F: Fn(&M, M::SnapshotType),
+    {
+        let mut lock = self.0.lock_mut();
+        let removed_keys = lock
+            .keys()
+            .filter(|key| !new_snapshot.contains_key(key))
+            .cloned()
+            .collect::<Vec<K>>();
+        for key in removed_keys {
+            lock.remove(&key);
+        }
+        for (key, new_value) in new_snapshot {
+            match lock.get(&key) {
+                Some(entry) => update_entry(entry, new_value),
+                None => {
+                    lock.insert_cloned(key, Arc::new(M::from_snapshot(&new_value)));
+                }
+            }
+        }
+    }
+}
+
+impl<K, M> MutableStruct for MutableStructMap<K, M>
+where
+    K: Ord + Clone + 'static,
+    M: MutableStruct + 'static,
+    M::SnapshotType: Clone,
+{
+    type SnapshotType = BTreeMap<K, M::SnapshotType>;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        self.0
+            .lock_ref()
+            .iter()
+            .map(|(key, entry)| (key.clone(), M::snapshot(entry)))
+            .collect()
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableStructMap::new_with_values(
+            snapshot
+                .iter()
+                .map(|(key, value)| (key.clone(), M::from_snapshot(value)))
+                .collect(),
+        )
+    }
+
+    /// Updates the entries whose key is still present in place, and creates or removes
+    /// entries for keys that were added or removed.
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        self.reconcile(new_snapshot, |entry, new_value| M::update(entry, new_value));
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        // Entry signals borrow from the entry, so they are boxed to give them a 'static
+        // type.
+        self.0
+            .entries_cloned()
+            .map_signal(|(key, entry)| {
+                Box::pin(M::snapshot_signal(&entry).map(move |snapshot| (key.clone(), snapshot)))
+                    as Pin<Box<dyn Signal<Item = (K, M::SnapshotType)>>>
+            })
+            .to_signal_map(|entries| entries.iter().cloned().collect())
+    }
+}
+
+impl<K, M> PartialUpdate for MutableStructMap<K, M>
+where
+    K: Ord + Clone + 'static,
+    M: PartialUpdate + 'static,
+    M::SnapshotType: Clone,
+{
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        self.reconcile(new_snapshot, |entry, new_value| {
+            M::update_changed(entry, new_value)
+        });
+    }
+}
+
+/// Reports the keys whose entries changed, including keys that only one side contains.
+impl<K, M> Diff for MutableStructMap<K, M>
+where
+    K: Ord + Clone + Debug + 'static,
+    M: Diff + 'static,
+    M::SnapshotType: Clone,
+{
+    type Field = K;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        let lock = self.0.lock_ref();
+        let mut keys = lock.keys().chain(other.keys()).collect::<Vec<&K>>();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter(|key| match (lock.get(*key), other.get(*key)) {
+                (Some(entry), Some(other_value)) => !M::diff(entry, other_value).is_empty(),
+                _ => true,
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl<K, M> ApplyDiff for MutableStructMap<K, M>
+where
+    K: Ord + Clone + 'static,
+    M: MutableStruct + 'static,
+    M::SnapshotType: Clone,
+{
+    type Patch = BTreeMap<K, M::SnapshotType>;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.update(patch);
+    }
+}
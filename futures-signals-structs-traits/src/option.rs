@@ -0,0 +1,126 @@
+use crate::{ApplyDiff, AsMutableStruct, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::{self, Mutable, Signal, SignalExt};
+use std::fmt;
+use std::ops::Deref;
+use std::pin::Pin;
+
+/// Mutable version of an `Option` whose value is itself a struct. Changing between `None`
+/// and `Some` is tracked by the outer `Mutable`, while changes inside a `Some` value are
+/// tracked by the nested MutableStruct.
+///
+/// Derefs to the underlying `Mutable<Option<M>>`, so the nested struct can be reached with
+/// `lock_ref()`. Prefer that over `get_cloned()`, which returns an independent copy.
+pub struct MutableOption<M>(Mutable<Option<M>>);
+
+impl<M> MutableOption<M> {
+    pub fn new(value: Option<M>) -> Self {
+        MutableOption(Mutable::new(value))
+    }
+}
+
+impl<M> Deref for MutableOption<M> {
+    type Target = Mutable<Option<M>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for MutableOption<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MutableOption")
+            .field(&*self.0.lock_ref())
+            .finish()
+    }
+}
+
+impl<T: AsMutableStruct> AsMutableStruct for Option<T>
+where
+    T::MutableStructType: 'static,
+{
+    type MutableStructType = MutableOption<T::MutableStructType>;
+
+    fn as_mutable_struct(&self) -> Self::MutableStructType {
+        MutableOption::new(self.as_ref().map(AsMutableStruct::as_mutable_struct))
+    }
+}
+
+impl<M: MutableStruct + 'static> MutableStruct for MutableOption<M> {
+    type SnapshotType = Option<M::SnapshotType>;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        self.0.lock_ref().as_ref().map(MutableStruct::snapshot)
+    }
+
+    fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+        match (&*self.0.lock_ref(), out) {
+            (Some(nested), Some(out)) => nested.snapshot_into(out),
+            (value, out) => *out = value.as_ref().map(MutableStruct::snapshot),
+        }
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableOption::new(snapshot.as_ref().map(M::from_snapshot))
+    }
+
+    /// Updates the nested struct in place when both the current and new values are `Some`,
+    /// so existing subscribers to the nested fields keep receiving changes. Otherwise the
+    /// whole value is replaced.
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        let mut lock = self.0.lock_mut();
+        match (&*lock, new_snapshot) {
+            (Some(existing), Some(new_value)) => existing.update(new_value),
+            (_, new_value) => *lock = new_value.as_ref().map(M::from_snapshot),
+        }
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        // The nested signal borrows from the lock guard, so it is boxed to give every
+        // branch of the switch the same 'static type.
+        self.0
+            .signal_ref(|value| {
+                value.as_ref().map(|nested| {
+                    Box::pin(nested.snapshot_signal())
+                        as Pin<Box<dyn Signal<Item = M::SnapshotType>>>
+                })
+            })
+            .switch(signal::option)
+    }
+}
+
+impl<M: PartialUpdate + 'static> PartialUpdate for MutableOption<M> {
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        let mut lock = self.0.lock_mut();
+        match (&*lock, new_snapshot) {
+            (Some(existing), Some(new_value)) => existing.update_changed(new_value),
+            (None, None) => {}
+            (_, new_value) => *lock = new_value.as_ref().map(M::from_snapshot),
+        }
+    }
+}
+
+/// Reports `None` when only one side is `Some`, or the changes inside the nested struct
+/// when both are.
+impl<M: Diff + 'static> Diff for MutableOption<M> {
+    type Field = Option<M::Field>;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        match (&*self.0.lock_ref(), other) {
+            (Some(existing), Some(other_value)) => {
+                existing.diff(other_value).into_iter().map(Some).collect()
+            }
+            (None, None) => vec![],
+            _ => vec![None],
+        }
+    }
+}
+
+/// Options are patched with a whole new value, since a patch for the nested struct could
+/// not be applied while the option is `None`.
+impl<M: MutableStruct + 'static> ApplyDiff for MutableOption<M> {
+    type Patch = Option<M::SnapshotType>;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.update(patch);
+    }
+}
@@ -0,0 +1,53 @@
+use futures_signals::signal::Signal;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// Polls the snapshot signal of a MutableStruct by hand, for integrating with a custom event
+/// loop instead of an async executor. Created by `MutableStruct::snapshot_poller`.
+///
+/// Signals are lazy, so there is no way to run a callback at the moment a field changes.
+/// Instead the poller remembers whether the struct changed since it was last polled, and
+/// the first poll always produces the current snapshot.
+pub struct SnapshotPoller<T> {
+    signal: Pin<Box<dyn Signal<Item = T>>>,
+}
+
+impl<T> SnapshotPoller<T> {
+    pub fn new(signal: impl Signal<Item = T> + 'static) -> Self {
+        SnapshotPoller {
+            signal: Box::pin(signal),
+        }
+    }
+
+    /// Returns `Ready(Some(snapshot))` if the struct changed since the last poll, and
+    /// otherwise `Pending`, waking `cx` once it changes again. Returns `Ready(None)` once
+    /// the struct has been dropped.
+    pub fn poll_snapshot(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.signal.as_mut().poll_change(cx)
+    }
+
+    /// Returns a fresh snapshot if the struct changed since the last call, without
+    /// registering for a wakeup. Suited to loops that check once per frame or tick.
+    pub fn try_next(&mut self) -> Option<T> {
+        let waker = noop_waker();
+        match self.poll_snapshot(&mut Context::from_waker(&waker)) {
+            Poll::Ready(snapshot) => snapshot,
+            Poll::Pending => None,
+        }
+    }
+}
+
+impl<T> fmt::Debug for SnapshotPoller<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotPoller").finish_non_exhaustive()
+    }
+}
+
+/// A waker that does nothing when woken, for polling without registering for a wakeup.
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+    const RAW: RawWaker = RawWaker::new(std::ptr::null(), &VTABLE);
+    // SAFETY: every function in the vtable ignores the data pointer, so it is never read.
+    unsafe { Waker::from_raw(RAW) }
+}
@@ -0,0 +1,23 @@
+use crate::AsMutableStruct;
+use futures_signals::signal::Mutable;
+use std::collections::{BTreeSet, HashSet};
+use std::hash::Hash;
+
+/// Sets are stored in a single `Mutable`, since futures-signals has no signal type for sets.
+/// Inserting or removing an element through `lock_mut` replaces the set as a whole for its
+/// subscribers.
+impl<T: Clone + Eq + Hash + 'static> AsMutableStruct for HashSet<T> {
+    type MutableStructType = Mutable<HashSet<T>>;
+
+    fn as_mutable_struct(&self) -> Self::MutableStructType {
+        Mutable::new(self.clone())
+    }
+}
+
+impl<T: Clone + Ord + 'static> AsMutableStruct for BTreeSet<T> {
+    type MutableStructType = Mutable<BTreeSet<T>>;
+
+    fn as_mutable_struct(&self) -> Self::MutableStructType {
+        Mutable::new(self.clone())
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{ApplyDiff, AsMutableStruct, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::{Signal, SignalExt};
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// An `Arc` or `Rc` of a struct becomes an `Arc` or `Rc` of the nested mutable struct, which
+/// can then be shared by cloning the pointer. The nested struct may change after it was
+/// converted, so every snapshot allocates a new pointer around a fresh snapshot instead of
+/// aliasing the original one. Pointers to basic values are stored in a `Mutable` by the
+/// derive instead, and their snapshots share the pointer.
+macro_rules! impl_shared_mutable_struct {
+    ($pointer:ident) => {
+        impl<T: AsMutableStruct + Clone + 'static> AsMutableStruct for $pointer<T> {
+            type MutableStructType = $pointer<T::MutableStructType>;
+
+            fn as_mutable_struct(&self) -> Self::MutableStructType {
+                $pointer::new((**self).as_mutable_struct())
+            }
+        }
+
+        impl<M: MutableStruct> MutableStruct for $pointer<M>
+        where
+            M::SnapshotType: Clone + 'static,
+        {
+            type SnapshotType = $pointer<M::SnapshotType>;
+
+            fn snapshot(&self) -> Self::SnapshotType {
+                $pointer::new((**self).snapshot())
+            }
+
+            /// Writes into the pointer in place if nothing else shares it, and otherwise
+            /// replaces it with a new pointer.
+            fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+                match $pointer::get_mut(out) {
+                    Some(inner) => (**self).snapshot_into(inner),
+                    None => *out = self.snapshot(),
+                }
+            }
+
+            fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+                $pointer::new(M::from_snapshot(snapshot))
+            }
+
+            fn update(&self, new_snapshot: Self::SnapshotType) {
+                (**self).update(
+                    $pointer::try_unwrap(new_snapshot).unwrap_or_else(|shared| (*shared).clone()),
+                );
+            }
+
+            fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+                (**self).snapshot_signal().map($pointer::new)
+            }
+        }
+
+        impl<M: PartialUpdate> PartialUpdate for $pointer<M>
+        where
+            M::SnapshotType: Clone + 'static,
+        {
+            fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+                (**self).update_changed(
+                    $pointer::try_unwrap(new_snapshot).unwrap_or_else(|shared| (*shared).clone()),
+                );
+            }
+        }
+
+        impl<M: Diff> Diff for $pointer<M>
+        where
+            M::SnapshotType: Clone + 'static,
+        {
+            type Field = M::Field;
+
+            fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+                (**self).diff(other)
+            }
+        }
+
+        impl<M: ApplyDiff> ApplyDiff for $pointer<M>
+        where
+            M::SnapshotType: Clone + 'static,
+        {
+            type Patch = M::Patch;
+
+            fn apply_diff(&self, patch: Self::Patch) {
+                (**self).apply_diff(patch);
+            }
+        }
+    };
+}
+
+impl_shared_mutable_struct!(Arc);
+impl_shared_mutable_struct!(Rc);
@@ -0,0 +1,141 @@
+use crate::MutableStruct;
+use futures_signals::signal::{Mutable, MutableSignal, Signal};
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+/// A mutex-guarded snapshot that is kept in sync with a mutable struct, for sharing it with
+/// code that doesn't use signals. Created by `MutableStruct::into_synced_mutex`.
+///
+/// Changes to the struct are copied into the mutex, and snapshots written through the mutex
+/// update the struct once the guard is dropped. Only guards that were used mutably count as
+/// writes. A write replaces the whole struct, including changes to it that were made since
+/// the mutex was last synced.
+pub struct SyncedMutex<T> {
+    value: Mutex<T>,
+    writes: Mutable<u64>,
+}
+
+impl<T> SyncedMutex<T> {
+    fn new(value: T) -> Self {
+        SyncedMutex {
+            value: Mutex::new(value),
+            writes: Mutable::new(0),
+        }
+    }
+
+    /// Locks the snapshot, blocking until it is available. Writes made through the guard
+    /// are sent to the mutable struct when it is dropped.
+    pub fn lock(&self) -> SyncedMutexGuard<'_, T> {
+        SyncedMutexGuard {
+            guard: self.value.lock().unwrap(),
+            writes: &self.writes,
+            written: false,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SyncedMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SyncedMutex")
+            .field(&*self.value.lock().unwrap())
+            .finish()
+    }
+}
+
+/// Guard returned by `SyncedMutex::lock`, which derefs to the snapshot.
+pub struct SyncedMutexGuard<'a, T> {
+    guard: MutexGuard<'a, T>,
+    writes: &'a Mutable<u64>,
+    written: bool,
+}
+
+impl<T> Deref for SyncedMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for SyncedMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.written = true;
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for SyncedMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.written {
+            *self.writes.lock_mut() += 1;
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SyncedMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SyncedMutexGuard")
+            .field(&*self.guard)
+            .finish()
+    }
+}
+
+/// Creates a `SyncedMutex` holding a snapshot of `mutable`, and the future that keeps the
+/// two in sync, which owns `mutable`.
+pub(crate) fn sync_with_mutex<M>(mutable: M) -> (Arc<SyncedMutex<M::SnapshotType>>, MutexSync<M>)
+where
+    M: MutableStruct,
+    M::SnapshotType: Clone + 'static,
+{
+    let mutex = Arc::new(SyncedMutex::new(mutable.snapshot()));
+    let sync = MutexSync {
+        snapshots: Box::pin(mutable.snapshot_signal()),
+        writes: mutex.writes.signal(),
+        seen_writes: mutex.writes.get(),
+        mutex: Arc::clone(&mutex),
+        mutable,
+    };
+    (mutex, sync)
+}
+
+/// Future that copies every change of a mutable struct into a `SyncedMutex`, and every
+/// write to the mutex back into the struct. It runs for as long as it is polled.
+pub(crate) struct MutexSync<M: MutableStruct> {
+    mutable: M,
+    snapshots: Pin<Box<dyn Signal<Item = M::SnapshotType>>>,
+    writes: MutableSignal<u64>,
+    seen_writes: u64,
+    mutex: Arc<SyncedMutex<M::SnapshotType>>,
+}
+
+// The struct itself is never pinned, and both signals already are.
+impl<M: MutableStruct> Unpin for MutexSync<M> {}
+
+impl<M: MutableStruct> Future for MutexSync<M>
+where
+    M::SnapshotType: Clone,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        // Writes are handled first, so that copying a snapshot into the mutex can't replace
+        // a write that hasn't reached the struct yet. The snapshot that follows then
+        // includes the write.
+        while let Poll::Ready(Some(writes)) = Pin::new(&mut this.writes).poll_change(cx) {
+            if writes != this.seen_writes {
+                this.seen_writes = writes;
+                let snapshot = this.mutex.value.lock().unwrap().clone();
+                this.mutable.update(snapshot);
+            }
+        }
+        while let Poll::Ready(Some(snapshot)) = this.snapshots.as_mut().poll_change(cx) {
+            *this.mutex.value.lock().unwrap() = snapshot;
+        }
+        Poll::Pending
+    }
+}
@@ -1,7 +1,15 @@
-use crate::{AsMutableStruct, MutableStruct};
-use futures_signals::signal_vec::MutableVec;
+use crate::{ApplyDiff, AsMutableStruct, Diff, MutableStruct, PartialUpdate};
+use futures_signals::signal::Signal;
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+use std::fmt;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
 
-impl<T: Clone> AsMutableStruct for Vec<T> {
+/// A `Vec` becomes a `MutableVec` of its plain elements. This only goes one level deep, so
+/// the inner vectors of a `Vec<Vec<T>>` are stored as plain values and replaced as a whole
+/// when they change. Use a `MutableStructVec<MutableVec<T>>` to observe them individually.
+impl<T: Clone + 'static> AsMutableStruct for Vec<T> {
     type MutableStructType = MutableVec<T>;
 
     fn as_mutable_struct(&self) -> Self::MutableStructType {
@@ -9,14 +17,323 @@ impl<T: Clone> AsMutableStruct for Vec<T> {
     }
 }
 
-impl<T: Clone> MutableStruct for MutableVec<T> {
+impl<T: Clone + 'static> MutableStruct for MutableVec<T> {
     type SnapshotType = Vec<T>;
 
     fn snapshot(&self) -> Self::SnapshotType {
         self.lock_ref().as_slice().to_vec()
     }
 
+    fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+        out.clear();
+        out.extend_from_slice(&self.lock_ref());
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableVec::new_with_values(snapshot.clone())
+    }
+
     fn update(&self, new_snapshot: Self::SnapshotType) {
         self.lock_mut().replace_cloned(new_snapshot);
     }
-}
\ No newline at end of file
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        self.signal_vec_cloned().to_signal_cloned()
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> PartialUpdate for MutableVec<T> {
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        let mut lock = self.lock_mut();
+        if lock.as_slice() != new_snapshot.as_slice() {
+            lock.replace_cloned(new_snapshot);
+        }
+    }
+}
+
+/// Vectors report the indices of elements that differ, including every index past the end
+/// of the shorter vector.
+impl<T: Clone + PartialEq + 'static> Diff for MutableVec<T> {
+    type Field = usize;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        let lock = self.lock_ref();
+        let max_len = lock.len().max(other.len());
+        (0..max_len)
+            .filter(|index| lock.get(*index) != other.get(*index))
+            .collect()
+    }
+}
+
+/// Vectors are patched with a whole new list of elements.
+impl<T: Clone + 'static> ApplyDiff for MutableVec<T> {
+    type Patch = Vec<T>;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.update(patch);
+    }
+}
+
+/// Mutable version of a `Vec` of structs where every element is itself a MutableStruct, so
+/// changes to the fields of individual elements can be observed. Use it by annotating a
+/// `Vec<T>` field with `#[mutable_type = "MutableStructVec<MutableT>"]`. Any MutableStruct
+/// works as an element, so `MutableStructVec<MutableVec<T>>` stores a `Vec<Vec<T>>` with
+/// observable inner vectors.
+///
+/// Elements are stored in an `Arc` so that signals of the whole vector keep following the
+/// original elements rather than copies of them. Derefs to the underlying `MutableVec`.
+pub struct MutableStructVec<M>(MutableVec<Arc<M>>);
+
+impl<M> MutableStructVec<M> {
+    pub fn new_with_values(values: Vec<M>) -> Self {
+        MutableStructVec(MutableVec::new_with_values(
+            values.into_iter().map(Arc::new).collect(),
+        ))
+    }
+}
+
+impl<M> Deref for MutableStructVec<M> {
+    type Target = MutableVec<Arc<M>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for MutableStructVec<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MutableStructVec")
+            .field(&self.0.lock_ref().as_slice())
+            .finish()
+    }
+}
+
+impl<M: MutableStruct + 'static> MutableStruct for MutableStructVec<M>
+where
+    M::SnapshotType: Clone,
+{
+    type SnapshotType = Vec<M::SnapshotType>;
+
+    fn snapshot(&self) -> Self::SnapshotType {
+        self.0
+            .lock_ref()
+            .iter()
+            .map(|element| M::snapshot(element))
+            .collect()
+    }
+
+    /// Writes into the snapshots of existing elements in place, then removes or appends
+    /// snapshots to match the current length.
+    fn snapshot_into(&self, out: &mut Self::SnapshotType) {
+        let elements = self.0.lock_ref();
+        out.truncate(elements.len());
+        for (element, out) in elements.iter().zip(out.iter_mut()) {
+            M::snapshot_into(element, out);
+        }
+        let written = out.len();
+        out.extend(
+            elements[written..]
+                .iter()
+                .map(|element| M::snapshot(element)),
+        );
+    }
+
+    fn from_snapshot(snapshot: &Self::SnapshotType) -> Self {
+        MutableStructVec::new_with_values(snapshot.iter().map(M::from_snapshot).collect())
+    }
+
+    /// Updates existing elements in place, then removes or appends elements to match the
+    /// length of the new snapshot.
+    fn update(&self, new_snapshot: Self::SnapshotType) {
+        let mut lock = self.0.lock_mut();
+        let new_len = new_snapshot.len();
+        let mut new_values = new_snapshot.into_iter();
+        for element in lock.iter() {
+            match new_values.next() {
+                Some(new_value) => M::update(element, new_value),
+                None => break,
+            }
+        }
+        if lock.len() > new_len {
+            lock.truncate(new_len);
+        }
+        for new_value in new_values {
+            lock.push_cloned(Arc::new(M::from_snapshot(&new_value)));
+        }
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Self::SnapshotType> + 'static {
+        // Element signals borrow from the element, so they are boxed to give them a
+        // 'static type.
+        self.0
+            .signal_vec_cloned()
+            .map_signal(|element| {
+                Box::pin(M::snapshot_signal(&element))
+                    as Pin<Box<dyn Signal<Item = M::SnapshotType>>>
+            })
+            .to_signal_cloned()
+    }
+}
+
+impl<M: PartialUpdate + 'static> PartialUpdate for MutableStructVec<M>
+where
+    M::SnapshotType: Clone,
+{
+    fn update_changed(&self, new_snapshot: Self::SnapshotType) {
+        let current_len = self.0.lock_ref().len();
+        if current_len == new_snapshot.len() {
+            for (element, new_value) in self.0.lock_ref().iter().zip(new_snapshot) {
+                M::update_changed(element, new_value);
+            }
+        } else {
+            self.update(new_snapshot);
+        }
+    }
+}
+
+impl<M: Diff + 'static> Diff for MutableStructVec<M>
+where
+    M::SnapshotType: Clone,
+{
+    type Field = usize;
+
+    fn diff(&self, other: &Self::SnapshotType) -> Vec<Self::Field> {
+        let lock = self.0.lock_ref();
+        let max_len = lock.len().max(other.len());
+        (0..max_len)
+            .filter(|index| match (lock.get(*index), other.get(*index)) {
+                (Some(element), Some(other_element)) => !M::diff(element, other_element).is_empty(),
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+impl<M: MutableStruct + 'static> ApplyDiff for MutableStructVec<M>
+where
+    M::SnapshotType: Clone,
+{
+    type Patch = Vec<M::SnapshotType>;
+
+    fn apply_diff(&self, patch: Self::Patch) {
+        self.update(patch);
+    }
+}
+
+/// Converts a plain `MutableVec` of mutable structs to and from a `Vec` of their snapshots.
+/// `MutableVec<M>` already implements `MutableStruct` with `Vec<M>` as its snapshot, so this
+/// is a separate trait, which derived structs use for fields annotated with
+/// `#[mutable_type = "MutableVec<MutableT>"]`.
+///
+/// Signals of a `MutableVec` produce clones of its elements, and cloning a mutable struct
+/// copies it, so `snapshot_signal` only produces a new snapshot when the vector itself
+/// changes, such as when elements are pushed or removed. Use `MutableStructVec` to also
+/// follow the fields of each element.
+pub trait MutableStructElements {
+    type Element: MutableStruct;
+
+    /// Returns a snapshot of every element.
+    fn snapshot(&self) -> Vec<<Self::Element as MutableStruct>::SnapshotType>;
+
+    /// Creates a new vector with a mutable struct for each snapshot.
+    fn from_snapshot(snapshot: &[<Self::Element as MutableStruct>::SnapshotType]) -> Self;
+
+    /// Updates existing elements in place, then removes or appends elements to match the
+    /// length of the new snapshot.
+    fn update(&self, new_snapshot: Vec<<Self::Element as MutableStruct>::SnapshotType>);
+
+    /// Updates every element like `update`, and returns a snapshot of the replaced elements.
+    fn replace_with(
+        &self,
+        new_snapshot: Vec<<Self::Element as MutableStruct>::SnapshotType>,
+    ) -> Vec<<Self::Element as MutableStruct>::SnapshotType> {
+        let previous = self.snapshot();
+        self.update(new_snapshot);
+        previous
+    }
+
+    /// Returns a signal that produces a new snapshot whenever the vector changes.
+    fn snapshot_signal(
+        &self,
+    ) -> impl Signal<Item = Vec<<Self::Element as MutableStruct>::SnapshotType>> + 'static;
+
+    /// Like `update`, but only updates the fields of existing elements that changed.
+    fn update_changed(&self, new_snapshot: Vec<<Self::Element as MutableStruct>::SnapshotType>)
+    where
+        Self::Element: PartialUpdate;
+
+    /// Returns the indices of elements that differ from `other`, including every index past
+    /// the end of the shorter vector.
+    fn diff(&self, other: &[<Self::Element as MutableStruct>::SnapshotType]) -> Vec<usize>
+    where
+        Self::Element: Diff;
+}
+
+impl<M: MutableStruct + Clone + 'static> MutableStructElements for MutableVec<M>
+where
+    M::SnapshotType: Clone,
+{
+    type Element = M;
+
+    fn snapshot(&self) -> Vec<M::SnapshotType> {
+        self.lock_ref().iter().map(M::snapshot).collect()
+    }
+
+    fn from_snapshot(snapshot: &[M::SnapshotType]) -> Self {
+        MutableVec::new_with_values(snapshot.iter().map(M::from_snapshot).collect())
+    }
+
+    fn update(&self, new_snapshot: Vec<M::SnapshotType>) {
+        let mut lock = self.lock_mut();
+        let new_len = new_snapshot.len();
+        let mut new_values = new_snapshot.into_iter();
+        for element in lock.iter() {
+            match new_values.next() {
+                Some(new_value) => M::update(element, new_value),
+                None => break,
+            }
+        }
+        if lock.len() > new_len {
+            lock.truncate(new_len);
+        }
+        for new_value in new_values {
+            lock.push_cloned(M::from_snapshot(&new_value));
+        }
+    }
+
+    fn snapshot_signal(&self) -> impl Signal<Item = Vec<M::SnapshotType>> + 'static {
+        // The elements of the signal are copies, so the snapshots are taken from the shared
+        // vector instead.
+        let elements = self.clone();
+        self.signal_vec_cloned()
+            .to_signal_map(move |_| MutableStructElements::snapshot(&elements))
+    }
+
+    fn update_changed(&self, new_snapshot: Vec<M::SnapshotType>)
+    where
+        M: PartialUpdate,
+    {
+        let current_len = self.lock_ref().len();
+        if current_len == new_snapshot.len() {
+            for (element, new_value) in self.lock_ref().iter().zip(new_snapshot) {
+                M::update_changed(element, new_value);
+            }
+        } else {
+            MutableStructElements::update(self, new_snapshot);
+        }
+    }
+
+    fn diff(&self, other: &[M::SnapshotType]) -> Vec<usize>
+    where
+        M: Diff,
+    {
+        let lock = self.lock_ref();
+        let max_len = lock.len().max(other.len());
+        (0..max_len)
+            .filter(|index| match (lock.get(*index), other.get(*index)) {
+                (Some(element), Some(other_element)) => !M::diff(element, other_element).is_empty(),
+                _ => true,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,132 @@
+use futures_signals::signal::{Mutable, MutableSignal, Signal};
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The version counter and batch state of a mutable struct annotated with
+/// `#[signal_versioned]`, which the derive keeps in a hidden field. Cloning it shares the
+/// counter, like cloning a `Mutable` shares its value.
+#[derive(Clone)]
+pub struct StructVersion {
+    version: Mutable<u64>,
+    batch_depth: Mutable<usize>,
+}
+
+impl StructVersion {
+    pub fn new() -> Self {
+        StructVersion {
+            version: Mutable::new(0),
+            batch_depth: Mutable::new(0),
+        }
+    }
+
+    /// Returns the current version.
+    pub fn get(&self) -> u64 {
+        self.version.get()
+    }
+
+    /// Returns a signal of the current version.
+    pub fn signal(&self) -> impl Signal<Item = u64> + 'static {
+        self.version.signal()
+    }
+
+    /// Increases the version by one, unless a batch is running, which counts as a single
+    /// change once it ends.
+    pub fn bump(&self) {
+        let batch_depth = self.batch_depth.lock_ref();
+        if *batch_depth == 0 {
+            drop(batch_depth);
+            self.version.replace_with(|version| *version + 1);
+        }
+    }
+
+    /// Runs `f` as a batch. Signals wrapped with `hold` don't produce any values until the
+    /// outermost batch ends, and the version then increases by one. Fields set directly
+    /// through their `Mutable` don't report their changes, so the batch counts as a change
+    /// even if nothing inside it bumped the version.
+    pub fn batch<R>(&self, f: impl FnOnce() -> R) -> R {
+        *self.batch_depth.lock_mut() += 1;
+        // Ends the batch even if `f` panics, so held signals don't stay stuck.
+        struct EndBatch<'a>(&'a StructVersion);
+
+        impl Drop for EndBatch<'_> {
+            fn drop(&mut self) {
+                let mut batch_depth = self.0.batch_depth.lock_mut();
+                *batch_depth -= 1;
+                let ended = *batch_depth == 0;
+                drop(batch_depth);
+                if ended {
+                    self.0.version.replace_with(|version| *version + 1);
+                }
+            }
+        }
+
+        let _end_batch = EndBatch(self);
+        f()
+    }
+
+    /// Wraps a signal of the struct so that it waits for any running batch to end before
+    /// producing a value, which then includes every change made in the batch.
+    pub fn hold<S: Signal>(&self, signal: S) -> HeldSignal<S> {
+        HeldSignal {
+            signal: Box::pin(signal),
+            batch_depth: self.batch_depth.clone(),
+            batch_depth_signal: self.batch_depth.signal(),
+        }
+    }
+}
+
+impl Default for StructVersion {
+    fn default() -> Self {
+        StructVersion::new()
+    }
+}
+
+impl fmt::Debug for StructVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StructVersion").field(&self.get()).finish()
+    }
+}
+
+/// Versions only track changes, so any two compare equal, which lets mutable structs that
+/// forward `PartialEq`, `Eq` or `Hash` with `#[mutable_derive]` compare their fields alone.
+impl PartialEq for StructVersion {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for StructVersion {}
+
+impl std::hash::Hash for StructVersion {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+/// A signal that is held back while a batch is running on its struct. Created by
+/// `StructVersion::hold`.
+pub struct HeldSignal<S> {
+    signal: Pin<Box<S>>,
+    batch_depth: Mutable<usize>,
+    batch_depth_signal: MutableSignal<usize>,
+}
+
+impl<S: Signal> Signal for HeldSignal<S> {
+    type Item = S::Item;
+
+    fn poll_change(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        // Polling until the batch depth is pending registers for a wakeup once it changes,
+        // which is how a held signal learns that a batch ended.
+        while let Poll::Ready(Some(_)) = Pin::new(&mut this.batch_depth_signal).poll_change(cx) {}
+        if this.batch_depth.get() > 0 {
+            return Poll::Pending;
+        }
+        this.signal.as_mut().poll_change(cx)
+    }
+}
+
+impl<S> fmt::Debug for HeldSignal<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeldSignal").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,79 @@
+use futures_signals::signal::{Signal, SignalExt};
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Binds fields of a mutable struct to callbacks, which run with the field's current value
+/// and then again after every change. Each arm names a field and the callback to run, and
+/// the result is a `Watchers` future that drives every callback until the struct is
+/// dropped. Nested fields pass their snapshot to the callback.
+/// ```ignore
+///     let watching = watch!(mutable_score,
+///         points => |points| println!("points: {}", points),
+///         multiplier => |multiplier| println!("multiplier: {}", multiplier),
+///     );
+///     spawn_local(watching);
+/// ```
+#[macro_export]
+macro_rules! watch {
+    ($mutable:expr, $($field:tt => $callback:expr),+ $(,)?) => {{
+        let mutable = &$mutable;
+        $crate::watch::Watchers::new()
+            $(.watch($crate::MutableStruct::snapshot_signal(&mutable.$field), $callback))+
+    }};
+}
+
+/// Future returned by `watch!`, which calls each of its callbacks with the values of a
+/// signal, and completes once every signal has ended.
+#[must_use = "Watchers do nothing unless polled"]
+pub struct Watchers<'a> {
+    futures: Vec<Pin<Box<dyn Future<Output = ()> + 'a>>>,
+}
+
+impl<'a> Watchers<'a> {
+    pub fn new() -> Self {
+        Watchers { futures: vec![] }
+    }
+
+    /// Adds a callback that runs with every value of `signal`.
+    pub fn watch<S, F>(mut self, signal: S, mut callback: F) -> Self
+    where
+        S: Signal + 'a,
+        F: FnMut(S::Item) + 'a,
+    {
+        self.futures.push(Box::pin(signal.for_each(move |value| {
+            callback(value);
+            future::ready(())
+        })));
+        self
+    }
+}
+
+impl Default for Watchers<'_> {
+    fn default() -> Self {
+        Watchers::new()
+    }
+}
+
+impl Future for Watchers<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.futures
+            .retain_mut(|future| future.as_mut().poll(cx).is_pending());
+        if self.futures.is_empty() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl fmt::Debug for Watchers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchers")
+            .field("remaining", &self.futures.len())
+            .finish()
+    }
+}
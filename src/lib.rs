@@ -1,5 +1,19 @@
 extern crate futures_signals_structs_traits;
 pub extern crate futures_signals_structs_derive;
 
-pub use futures_signals_structs_traits::{AsMutableStruct, MutableStruct};
-pub use futures_signals_structs_derive::AsMutableStruct;
\ No newline at end of file
+pub use futures_signals_structs_traits::{
+    ApplyDiff, AsMutableStruct, Diff, GetFieldSignal, MutableStruct, PartialUpdate,
+    ValidationError, Versioned,
+};
+pub use futures_signals_structs_traits::deque::MutableVecDeque;
+pub use futures_signals_structs_traits::keyed::{Keyed, MutableKeyedVec};
+pub use futures_signals_structs_traits::lazy::MutableLazy;
+pub use futures_signals_structs_traits::map::MutableStructMap;
+pub use futures_signals_structs_traits::option::MutableOption;
+pub use futures_signals_structs_traits::poll::SnapshotPoller;
+pub use futures_signals_structs_traits::synced::{SyncedMutex, SyncedMutexGuard};
+pub use futures_signals_structs_traits::vec::MutableStructVec;
+pub use futures_signals_structs_traits::version::StructVersion;
+pub use futures_signals_structs_traits::watch;
+pub use futures_signals_structs_traits::watch::Watchers;
+pub use futures_signals_structs_derive::AsMutableStruct;
@@ -3,8 +3,26 @@ extern crate futures_signals_structs_traits;
 extern crate futures_signals_structs_derive;
 extern crate futures_signals;
 
-use futures_signals::signal::Mutable;
-use futures_signals_structs_traits::{AsMutableStruct, MutableStruct};
+use futures::executor::block_on;
+use futures::future::FutureExt;
+use futures::stream::StreamExt;
+use futures_signals::signal::{Mutable, SignalExt};
+use futures_signals::signal_vec::{MutableVec, SignalVecExt, VecDiff};
+use futures_signals_structs_traits::{
+    ApplyDiff, AsMutableStruct, Diff, GetFieldSignal, MutableStruct, PartialUpdate, ValidationError,
+    Versioned,
+};
+use futures_signals_structs_traits::keyed::MutableKeyedVec;
+use futures_signals_structs_traits::map::MutableStructMap;
+use futures_signals_structs_traits::vec::MutableStructVec;
+use futures_signals_structs_traits::watch;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(AsMutableStruct, Debug, PartialEq, Clone)]
 struct PlayerScore {
@@ -87,6 +105,24 @@ fn allows_custom_names() {
     };
 }
 
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct NamedStruct {
+    name: String,
+}
+
+#[test]
+fn treats_strings_as_basic() {
+    let raw = NamedStruct {
+        name: "Player 1".to_string()
+    };
+    let mutable_named: MutableNamedStruct = raw.as_mutable_struct();
+    let name: &Mutable<String> = &mutable_named.name;
+    name.set("Player 2".to_string());
+    assert_eq!(mutable_named.snapshot(), NamedStruct {
+        name: "Player 2".to_string()
+    });
+}
+
 #[test]
 fn allows_composed_structs() {
     let composed_struct = ComposedStruct {
@@ -128,4 +164,2668 @@ fn updates_composed_structs() {
     };
     mutable_composed_struct.update(updated.clone());
     assert_eq!(mutable_composed_struct.snapshot(), updated);
-}
\ No newline at end of file
+}
+
+#[allow(non_camel_case_types)]
+type player_score = PlayerScore;
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct GameScore {
+    #[mutable_type = "MutablePlayerScore"] player_1: PlayerScore,
+    #[mutable_type = "MutablePlayerScore"] player_2: player_score,
+}
+
+#[test]
+fn honors_mutable_type() {
+    let game_score = GameScore {
+        player_1: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+        player_2: PlayerScore {
+            points: 20,
+            multiplier: 2.0
+        },
+    };
+    let mutable_game_score = game_score.as_mutable_struct();
+    let player_2: &MutablePlayerScore = &mutable_game_score.player_2;
+    player_2.points.set(30);
+    assert_eq!(mutable_game_score.snapshot(), GameScore {
+        player_1: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+        player_2: PlayerScore {
+            points: 30,
+            multiplier: 2.0
+        },
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct CachedScore {
+    points: u32,
+    #[signal_skip] cached_label: String,
+}
+
+#[test]
+fn skips_fields() {
+    let raw = CachedScore {
+        points: 10,
+        cached_label: "10 points".to_string()
+    };
+    let mutable_score = raw.as_mutable_struct();
+    let cached_label: &String = &mutable_score.cached_label;
+    assert_eq!(cached_label, "10 points");
+
+    mutable_score.update(CachedScore {
+        points: 20,
+        cached_label: "20 points".to_string()
+    });
+    assert_eq!(mutable_score.snapshot(), CachedScore {
+        points: 20,
+        cached_label: "10 points".to_string()
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Point(f32, f32);
+
+#[test]
+fn allows_tuple_structs() {
+    let mutable_point = Point(1.0, 2.0).as_mutable_struct();
+    mutable_point.0.set(3.0);
+    assert_eq!(mutable_point.1.get(), 2.0);
+    assert_eq!(mutable_point.snapshot(), Point(3.0, 2.0));
+
+    mutable_point.update(Point(4.0, 5.0));
+    assert_eq!(mutable_point.snapshot(), Point(4.0, 5.0));
+}
+
+#[test]
+fn formats_with_debug() {
+    let raw = PlayerScore {
+        points: 40,
+        multiplier: 1.5
+    };
+    let player_signal = raw.as_mutable_struct();
+    assert_eq!(
+        format!("{:?}", player_signal),
+        "MutablePlayerScore { points: 40, multiplier: 1.5 }"
+    );
+    assert_eq!(
+        format!("{:?}", Point(1.0, 2.5).as_mutable_struct()),
+        "MutablePoint(1.0, 2.5)"
+    );
+}
+
+#[test]
+fn compares_current_values() {
+    let player_signal_1 = PlayerScore {
+        points: 40,
+        multiplier: 1.5
+    }.as_mutable_struct();
+    let player_signal_2 = MutablePlayerScore {
+        points: Mutable::new(40),
+        multiplier: Mutable::new(1.5),
+    };
+    assert_eq!(player_signal_1, player_signal_2);
+
+    player_signal_2.points.set(41);
+    assert_ne!(player_signal_1, player_signal_2);
+}
+
+#[test]
+fn exposes_field_signals() {
+    let composed_struct = ComposedStruct {
+        score: PlayerScore {
+            points: 40,
+            multiplier: 0.4
+        },
+        events: vec![],
+    };
+    let mutable_composed_struct = composed_struct.as_mutable_struct();
+    let score = mutable_composed_struct.score();
+
+    let mut points = score.points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(40));
+
+    let named = NamedStruct {
+        name: "Player 1".to_string()
+    }.as_mutable_struct();
+    let mut name = named.name_signal_cloned().to_stream();
+    assert_eq!(block_on(name.next()), Some("Player 1".to_string()));
+}
+
+#[test]
+fn produces_snapshot_signal() {
+    let raw = PlayerScore {
+        points: 40,
+        multiplier: 1.5
+    };
+    let player_signal = raw.as_mutable_struct();
+    let mut snapshots = player_signal.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()), Some(PlayerScore {
+        points: 40,
+        multiplier: 1.5
+    }));
+
+    player_signal.points.set(50);
+    assert_eq!(block_on(snapshots.next()), Some(PlayerScore {
+        points: 50,
+        multiplier: 1.5
+    }));
+
+    player_signal.multiplier.set(2.0);
+    assert_eq!(block_on(snapshots.next()), Some(PlayerScore {
+        points: 50,
+        multiplier: 2.0
+    }));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_versioned]
+struct Scoreboard {
+    points: u32,
+    multiplier: f32,
+}
+
+#[test]
+fn emits_one_snapshot_per_batch() {
+    let scoreboard = Scoreboard {
+        points: 40,
+        multiplier: 1.5
+    }
+    .as_mutable_struct();
+    let mut snapshots = scoreboard.snapshot_signal().to_stream();
+    let mut points = scoreboard.points_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()), Some(Scoreboard {
+        points: 40,
+        multiplier: 1.5
+    }));
+    assert_eq!(block_on(points.next()), Some(40));
+
+    scoreboard.batch(|scoreboard| {
+        scoreboard.points.set(41);
+        assert_eq!(snapshots.next().now_or_never(), None);
+        assert_eq!(points.next().now_or_never(), None);
+        scoreboard.points.set(42);
+        scoreboard.multiplier.set(2.0);
+        assert_eq!(snapshots.next().now_or_never(), None);
+    });
+    assert_eq!(block_on(snapshots.next()), Some(Scoreboard {
+        points: 42,
+        multiplier: 2.0
+    }));
+    assert_eq!(block_on(points.next()), Some(42));
+    assert_eq!(snapshots.next().now_or_never(), None);
+}
+
+#[test]
+fn counts_versions_of_changes() {
+    let scoreboard = Scoreboard {
+        points: 40,
+        multiplier: 1.5,
+    }
+    .as_mutable_struct();
+    let mut versions = scoreboard.version_signal().to_stream();
+    assert_eq!(block_on(versions.next()), Some(0));
+
+    scoreboard.set_points(41);
+    assert_eq!(block_on(versions.next()), Some(1));
+
+    // A late subscriber starts from the same version instead of counting from 0.
+    let mut late_versions = scoreboard.version_signal().to_stream();
+    assert_eq!(block_on(late_versions.next()), Some(1));
+
+    scoreboard.update(Scoreboard {
+        points: 42,
+        multiplier: 1.5,
+    });
+    scoreboard.update_changed(Scoreboard {
+        points: 43,
+        multiplier: 1.5,
+    });
+    scoreboard.apply_diff(ApplyableScoreboard {
+        multiplier: Some(2.0),
+        ..Default::default()
+    });
+    assert_eq!(scoreboard.version(), 4);
+    assert_eq!(block_on(versions.next()), Some(4));
+    assert_eq!(block_on(late_versions.next()), Some(4));
+
+    scoreboard.batch(|scoreboard| {
+        scoreboard.set_points(44);
+        scoreboard.set_multiplier(2.5);
+    });
+    assert_eq!(block_on(versions.next()), Some(5));
+    assert_eq!(block_on(late_versions.next()), Some(5));
+
+    // A batch counts once even if it only sets Mutables directly.
+    scoreboard.batch(|scoreboard| {
+        scoreboard.points.set(45);
+        scoreboard.multiplier.set(3.0);
+    });
+    assert_eq!(block_on(versions.next()), Some(6));
+    assert_eq!(scoreboard.version(), 6);
+
+    // Setting a field's Mutable directly outside of a batch is not counted.
+    scoreboard.points.set(46);
+    assert_eq!(scoreboard.version(), 6);
+}
+
+#[test]
+fn maps_snapshots_into_views() {
+    let player_signal = PlayerScore {
+        points: 40,
+        multiplier: 1.5
+    }
+    .as_mutable_struct();
+    let mut totals = player_signal
+        .map_snapshot(|score| score.points as f32 * score.multiplier)
+        .to_stream();
+    assert_eq!(block_on(totals.next()), Some(60.0));
+
+    player_signal.points.set(50);
+    assert_eq!(block_on(totals.next()), Some(75.0));
+
+    player_signal.multiplier.set(2.0);
+    assert_eq!(block_on(totals.next()), Some(100.0));
+}
+
+#[test]
+fn dedupes_snapshot_signal() {
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    let mut snapshots = player_score.snapshot_signal_dedupe().to_stream();
+    assert_eq!(block_on(snapshots.next()).map(|score| score.points), Some(40));
+
+    player_score.points.set(40);
+    player_score.points.set(40);
+    assert_eq!(snapshots.next().now_or_never(), None);
+
+    player_score.points.set(50);
+    assert_eq!(block_on(snapshots.next()).map(|score| score.points), Some(50));
+}
+
+mod settings {
+    use futures_signals_structs::AsMutableStruct;
+
+    #[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+    #[mutable_vis = "pub"]
+    pub struct Settings {
+        volume: u8,
+    }
+
+    pub fn default_settings() -> MutableSettings {
+        Settings { volume: 5 }.as_mutable_struct()
+    }
+
+    #[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+    #[mutable(name = "LiveDisplay", vis = "pub", derive(Debug))]
+    pub struct Display {
+        brightness: u8,
+    }
+
+    pub fn default_display() -> LiveDisplay {
+        Display { brightness: 80 }.as_mutable_struct()
+    }
+}
+
+#[test]
+fn zips_snapshots_of_two_structs() {
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    let settings = settings::default_settings();
+    let mut pairs = player_score.zip_snapshot_signal(&settings).to_stream();
+    let (score, current_settings) = block_on(pairs.next()).unwrap();
+    assert_eq!(score.points, 40);
+    assert_eq!(current_settings, settings.snapshot());
+
+    settings.volume.set(8);
+    let (score, current_settings) = block_on(pairs.next()).unwrap();
+    assert_eq!(score.points, 40);
+    assert_eq!(current_settings, settings.snapshot());
+
+    player_score.points.set(50);
+    assert_eq!(block_on(pairs.next()).unwrap().0.points, 50);
+}
+
+#[test]
+fn tracks_dirty_state() {
+    let saved = PlayerScore {
+        points: 40,
+        multiplier: 1.5
+    };
+    let player_score = saved.clone().as_mutable_struct();
+    let mut dirty = player_score.is_dirty_signal(saved).to_stream();
+    assert_eq!(block_on(dirty.next()), Some(false));
+
+    player_score.points.set(50);
+    assert_eq!(block_on(dirty.next()), Some(true));
+
+    player_score.multiplier.set(2.0);
+    assert_eq!(dirty.next().now_or_never(), None);
+
+    player_score.points.set(40);
+    player_score.multiplier.set(1.5);
+    assert_eq!(block_on(dirty.next()), Some(false));
+}
+
+#[test]
+fn polls_snapshots_without_executor() {
+    let player_score = PlayerScore {
+        points: 40,
+        multiplier: 1.5
+    }
+    .as_mutable_struct();
+    let mut poller = player_score.snapshot_poller();
+    assert_eq!(poller.try_next().map(|score| score.points), Some(40));
+    assert_eq!(poller.try_next(), None);
+
+    player_score.points.set(50);
+    player_score.multiplier.set(2.0);
+    let waker = futures::task::noop_waker();
+    let snapshot = poller.poll_snapshot(&mut std::task::Context::from_waker(&waker));
+    assert_eq!(
+        snapshot,
+        std::task::Poll::Ready(Some(PlayerScore {
+            points: 50,
+            multiplier: 2.0
+        }))
+    );
+    assert_eq!(poller.try_next(), None);
+}
+
+#[test]
+fn produces_nested_snapshot_signal() {
+    let composed_struct = ComposedStruct {
+        score: PlayerScore {
+            points: 40,
+            multiplier: 0.4
+        },
+        events: vec![],
+    };
+    let mutable_composed_struct = composed_struct.as_mutable_struct();
+    let mut snapshots = mutable_composed_struct.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()), Some(composed_struct));
+
+    mutable_composed_struct.score.points.set(50);
+    mutable_composed_struct.events.lock_mut().push_cloned("First".to_string());
+    assert_eq!(block_on(snapshots.next()), Some(ComposedStruct {
+        score: PlayerScore {
+            points: 50,
+            multiplier: 0.4
+        },
+        events: vec!["First".to_string()],
+    }));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Profile {
+    nickname: Option<String>,
+    best_score: Option<PlayerScore>,
+}
+
+#[test]
+fn round_trips_options() {
+    let profile = Profile {
+        nickname: None,
+        best_score: None,
+    };
+    let mutable_profile = profile.as_mutable_struct();
+    let nickname: &Mutable<Option<String>> = &mutable_profile.nickname;
+    assert_eq!(nickname.get_cloned(), None);
+    assert_eq!(mutable_profile.snapshot(), profile);
+
+    let with_score = Profile {
+        nickname: Some("Ace".to_string()),
+        best_score: Some(PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        }),
+    };
+    mutable_profile.update(with_score.clone());
+    assert_eq!(mutable_profile.snapshot(), with_score);
+
+    mutable_profile.update(profile.clone());
+    assert_eq!(mutable_profile.snapshot(), profile);
+}
+
+#[test]
+fn preserves_nested_options() {
+    let mutable_profile = Profile {
+        nickname: None,
+        best_score: Some(PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        }),
+    }.as_mutable_struct();
+    let mut points = mutable_profile.best_score.lock_ref().as_ref().unwrap().points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(10));
+
+    mutable_profile.update(Profile {
+        nickname: None,
+        best_score: Some(PlayerScore {
+            points: 20,
+            multiplier: 1.0
+        }),
+    });
+    assert_eq!(block_on(points.next()), Some(20));
+
+    let mut snapshots = mutable_profile.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()).unwrap().best_score.unwrap().points, 20);
+    mutable_profile.best_score.set(None);
+    assert_eq!(block_on(snapshots.next()).unwrap().best_score, None);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Leaderboard {
+    scores: BTreeMap<String, u32>,
+}
+
+#[test]
+fn round_trips_btree_maps() {
+    let mut scores = BTreeMap::new();
+    scores.insert("Ace".to_string(), 10);
+    scores.insert("Bean".to_string(), 20);
+    let mutable_leaderboard = Leaderboard { scores }.as_mutable_struct();
+
+    mutable_leaderboard.scores.lock_mut().insert_cloned("Cat".to_string(), 30);
+    mutable_leaderboard.scores.lock_mut().remove(&"Ace".to_string());
+    let mut expected = BTreeMap::new();
+    expected.insert("Bean".to_string(), 20);
+    expected.insert("Cat".to_string(), 30);
+    assert_eq!(mutable_leaderboard.snapshot().scores, expected);
+
+    let mut snapshots = mutable_leaderboard.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()).unwrap().scores, expected);
+
+    let mut replacement = BTreeMap::new();
+    replacement.insert("Dot".to_string(), 40);
+    mutable_leaderboard.update(Leaderboard {
+        scores: replacement.clone(),
+    });
+    assert_eq!(mutable_leaderboard.snapshot().scores, replacement);
+    assert_eq!(block_on(snapshots.next()).unwrap().scores, replacement);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Team {
+    #[mutable_type = "MutableStructVec<MutablePlayerScore>"]
+    players: Vec<PlayerScore>,
+}
+
+#[test]
+fn observes_struct_vec_elements() {
+    let player = |points| PlayerScore {
+        points,
+        multiplier: 1.0
+    };
+    let mutable_team = Team {
+        players: vec![player(1), player(2), player(3), player(4)],
+    }.as_mutable_struct();
+    let mut snapshots = mutable_team.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()).unwrap().players.len(), 4);
+
+    let fourth_points = mutable_team.players.lock_ref()[3].points_signal();
+    let mut fourth_points = fourth_points.to_stream();
+    assert_eq!(block_on(fourth_points.next()), Some(4));
+
+    mutable_team.players.lock_ref()[3].points.set(40);
+    assert_eq!(block_on(fourth_points.next()), Some(40));
+    assert_eq!(block_on(snapshots.next()).unwrap(), Team {
+        players: vec![player(1), player(2), player(3), player(40)],
+    });
+
+    mutable_team.update(Team {
+        players: vec![player(5), player(6)],
+    });
+    assert_eq!(mutable_team.snapshot(), Team {
+        players: vec![player(5), player(6)],
+    });
+    mutable_team.update(Team {
+        players: vec![player(7), player(8), player(9)],
+    });
+    assert_eq!(mutable_team.snapshot(), Team {
+        players: vec![player(7), player(8), player(9)],
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Default)]
+struct DefaultScore {
+    points: u32,
+    multiplier: f32,
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Default)]
+struct ScoreLog {
+    score: DefaultScore,
+    events: Vec<String>,
+}
+
+#[test]
+fn defaults_from_snapshot_default() {
+    assert_eq!(MutableDefaultScore::default().snapshot(), DefaultScore::default());
+}
+
+#[test]
+fn resets_to_default() {
+    let composed_struct = ScoreLog {
+        score: DefaultScore {
+            points: 40,
+            multiplier: 0.4
+        },
+        events: vec![],
+    };
+    let mutable_composed_struct = composed_struct.as_mutable_struct();
+    let mut points = mutable_composed_struct.score.points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(40));
+
+    mutable_composed_struct.score.multiplier.set(2.0);
+    mutable_composed_struct.events.lock_mut().push_cloned("First".to_string());
+    mutable_composed_struct.reset();
+    assert_eq!(mutable_composed_struct.snapshot(), ScoreLog::default());
+    assert_eq!(block_on(points.next()), Some(0));
+}
+
+#[test]
+fn updates_only_changed_fields() {
+    let composed_struct = ComposedStruct {
+        score: PlayerScore {
+            points: 40,
+            multiplier: 0.4
+        },
+        events: vec![],
+    };
+    let mutable_composed_struct = composed_struct.as_mutable_struct();
+    let mut points = mutable_composed_struct.score.points_signal().to_stream();
+    let mut multiplier = mutable_composed_struct.score.multiplier_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(40));
+    assert_eq!(block_on(multiplier.next()), Some(0.4));
+
+    let updated = ComposedStruct {
+        score: PlayerScore {
+            points: 40,
+            multiplier: 0.9
+        },
+        events: vec!["First".to_string()],
+    };
+    mutable_composed_struct.update_changed(updated.clone());
+    assert_eq!(mutable_composed_struct.snapshot(), updated);
+    assert_eq!(block_on(multiplier.next()), Some(0.9));
+    assert_eq!(points.next().now_or_never(), None);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Character {
+    level: u8,
+    name: String,
+    best_score: PlayerScore,
+}
+
+#[test]
+fn updates_from_borrowed_snapshot() {
+    let character = Character {
+        level: 1,
+        name: "Ada".to_string(),
+        best_score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+    }
+    .as_mutable_struct();
+    let mut points = character.best_score.points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(10));
+
+    let updated = Character {
+        level: 2,
+        name: "Grace".to_string(),
+        best_score: PlayerScore {
+            points: 20,
+            multiplier: 1.0
+        },
+    };
+    character.update_ref(&updated);
+    assert_eq!(block_on(points.next()), Some(20));
+    assert_eq!(character.snapshot(), updated);
+    assert_eq!(updated.name, "Grace");
+}
+
+#[test]
+fn lists_field_debug_values() {
+    assert_eq!(MutablePlayerScore::field_names(), &["points", "multiplier"]);
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    assert_eq!(player_score.field_debug_values(), vec![
+        ("points", "40".to_string()),
+        ("multiplier", "1.5".to_string()),
+    ]);
+}
+
+#[test]
+fn merges_another_mutable_struct() {
+    let target = Character {
+        level: 1,
+        name: "Ada".to_string(),
+        best_score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+    }
+    .as_mutable_struct();
+    let source = Character {
+        level: 5,
+        name: "Grace".to_string(),
+        best_score: PlayerScore {
+            points: 30,
+            multiplier: 2.0
+        },
+    }
+    .as_mutable_struct();
+    let mut points = target.best_score.points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(10));
+
+    target.merge(&source);
+    assert_eq!(target.snapshot(), source.snapshot());
+    assert_eq!(block_on(points.next()), Some(30));
+}
+
+#[test]
+fn builds_mutable_structs() {
+    let mutable_score = MutablePlayerScoreBuilder::new()
+        .points(12)
+        .build();
+    assert_eq!(mutable_score.snapshot(), PlayerScore {
+        points: 12,
+        multiplier: 0.0
+    });
+}
+
+#[test]
+fn constructs_from_raw_values() {
+    let mutable_score = MutablePlayerScore::new(12, 0.5);
+    assert_eq!(mutable_score.snapshot(), PlayerScore {
+        points: 12,
+        multiplier: 0.5
+    });
+
+    let character = MutableCharacter::new(3, "Ada".to_string(), PlayerScore {
+        points: 40,
+        multiplier: 1.0
+    });
+    character.best_score.points.set(45);
+    assert_eq!(character.snapshot().best_score.points, 45);
+}
+
+#[test]
+fn streams_snapshots() {
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    let mut snapshots = player_score.snapshot_stream();
+    assert_eq!(block_on(snapshots.next()), Some(PlayerScore { points: 40, multiplier: 1.5 }));
+    player_score.points.set(50);
+    assert_eq!(block_on(snapshots.next()), Some(PlayerScore { points: 50, multiplier: 1.5 }));
+    drop(player_score);
+    assert_eq!(block_on(snapshots.collect::<Vec<_>>()), vec![]);
+}
+
+#[test]
+fn syncs_with_mutex() {
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    // Clones of a field's Mutable share its value, so the field can still be observed and
+    // changed once the struct is moved into the future.
+    let points = player_score.points.clone();
+    let (mutex, sync) = player_score.into_synced_mutex();
+    let mut sync = Box::pin(sync);
+    assert_eq!(sync.as_mut().now_or_never(), None);
+    assert_eq!(mutex.lock().points, 40);
+
+    points.set(50);
+    assert_eq!(sync.as_mut().now_or_never(), None);
+    assert_eq!(mutex.lock().points, 50);
+
+    mutex.lock().points = 60;
+    assert_eq!(sync.as_mut().now_or_never(), None);
+    assert_eq!(points.get(), 60);
+    assert_eq!(*mutex.lock(), PlayerScore { points: 60, multiplier: 1.5 });
+
+    // A write made before the sync is polled again isn't lost to the pending snapshot.
+    points.set(70);
+    mutex.lock().multiplier = 3.0;
+    assert_eq!(sync.as_mut().now_or_never(), None);
+    assert_eq!(*mutex.lock(), PlayerScore { points: 60, multiplier: 3.0 });
+    assert_eq!(points.get(), 60);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Label {
+    #[signal_store_as = "String"] text: Cow<'static, str>,
+    color: u32,
+}
+
+#[test]
+fn gets_and_sets_fields_through_methods() {
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    player_score.set_points(player_score.points() + 10);
+    player_score.set_multiplier(2.0);
+    assert_eq!(player_score.points(), 50);
+    assert_eq!(player_score.snapshot(), PlayerScore { points: 50, multiplier: 2.0 });
+
+    let label = Label {
+        text: Cow::Borrowed("Ready"),
+        color: 0,
+    }
+    .as_mutable_struct();
+    label.set_text(Cow::Borrowed("Paused"));
+    assert_eq!(label.text(), Cow::<str>::Owned("Paused".to_string()));
+    assert_eq!(label.text.get_cloned(), "Paused");
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Volume {
+    #[signal_min = "10"] #[signal_max = "100"] level: u32,
+    #[signal_max = "1.0"] balance: f32,
+}
+
+#[test]
+fn clamps_fields_to_limits() {
+    let volume = MutableVolume::new(150, 0.5);
+    assert_eq!(volume.level(), 100);
+    volume.set_level(5);
+    assert_eq!(volume.level(), 10);
+
+    volume.update(Volume { level: 500, balance: 3.0 });
+    assert_eq!(volume.snapshot(), Volume { level: 100, balance: 1.0 });
+    volume.update_changed(Volume { level: 50, balance: -3.0 });
+    assert_eq!(volume.snapshot(), Volume { level: 50, balance: -3.0 });
+}
+
+#[test]
+fn watches_fields() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    let points_log = Rc::clone(&log);
+    let multiplier_log = Rc::clone(&log);
+    let mut watching = watch!(player_score,
+        points => move |points| points_log.borrow_mut().push(format!("points {}", points)),
+        multiplier => move |multiplier| {
+            multiplier_log.borrow_mut().push(format!("multiplier {}", multiplier))
+        },
+    );
+    assert_eq!((&mut watching).now_or_never(), None);
+    assert_eq!(*log.borrow(), vec!["points 40", "multiplier 1.5"]);
+
+    player_score.points.set(50);
+    assert_eq!((&mut watching).now_or_never(), None);
+    assert_eq!(*log.borrow(), vec!["points 40", "multiplier 1.5", "points 50"]);
+
+    drop(player_score);
+    assert_eq!(watching.now_or_never(), Some(()));
+}
+
+#[test]
+fn throttles_field_signals() {
+    let clock = Mutable::new(0);
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    let sleep_clock = clock.clone();
+    let mut points = player_score
+        .points_signal_throttled(move || {
+            let wake_at = sleep_clock.get() + 1;
+            sleep_clock.signal().wait_for(wake_at).map(|_| ())
+        })
+        .to_stream();
+    assert_eq!(block_on(points.next()), Some(40));
+
+    player_score.points.set(50);
+    player_score.points.set(60);
+    assert_eq!(points.next().now_or_never(), None);
+
+    clock.set(1);
+    assert_eq!(block_on(points.next()), Some(60));
+}
+
+thread_local! {
+    static PAYLOAD_CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// Counts its clones, to check which accessors read it in place.
+#[derive(Debug, PartialEq)]
+struct Payload(Vec<u8>);
+
+impl Clone for Payload {
+    fn clone(&self) -> Self {
+        PAYLOAD_CLONES.with(|clones| clones.set(clones.get() + 1));
+        Payload(self.0.clone())
+    }
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Message {
+    #[signal_basic] payload: Payload,
+}
+
+#[test]
+fn reads_fields_without_cloning() {
+    let message = MutableMessage::new(Payload(vec![1; 1024]));
+    let clones = PAYLOAD_CLONES.with(|clones| clones.get());
+    assert_eq!(message.with_payload(|payload| payload.0.len()), 1024);
+
+    let mut lengths = message.payload_signal_ref(|payload| payload.0.len()).to_stream();
+    assert_eq!(block_on(lengths.next()), Some(1024));
+    message.payload.set(Payload(vec![2; 16]));
+    assert_eq!(block_on(lengths.next()), Some(16));
+    assert_eq!(PAYLOAD_CLONES.with(|clones| clones.get()), clones);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Upload {
+    name: String,
+    #[signal_wrap = "Arc"] bytes: Vec<u8>,
+}
+
+#[test]
+fn wraps_fields_in_smart_pointers() {
+    let upload = Upload {
+        name: "data.bin".to_string(),
+        bytes: vec![7; 4096],
+    };
+    let mutable_upload = upload.as_mutable_struct();
+    let bytes: &Mutable<Arc<Vec<u8>>> = &mutable_upload.bytes;
+    assert_eq!(bytes.lock_ref().len(), 4096);
+    assert_eq!(mutable_upload.snapshot(), upload);
+
+    let mut bytes_signal = mutable_upload.bytes_signal_cloned().to_stream();
+    assert_eq!(block_on(bytes_signal.next()), Some(Arc::new(vec![7; 4096])));
+
+    let updated = Upload {
+        name: "data.bin".to_string(),
+        bytes: vec![1, 2, 3],
+    };
+    mutable_upload.update(updated.clone());
+    assert_eq!(mutable_upload.snapshot(), updated);
+    assert_eq!(block_on(bytes_signal.next()), Some(Arc::new(vec![1, 2, 3])));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Default)]
+struct Wrapper<T> {
+    value: T,
+    label: String,
+}
+
+#[test]
+fn supports_generic_structs() {
+    let wrapped_points = Wrapper {
+        value: 5u32,
+        label: "points".to_string(),
+    };
+    let mutable_points = wrapped_points.as_mutable_struct();
+    mutable_points.value.set(6);
+    assert_eq!(mutable_points.snapshot(), Wrapper {
+        value: 6,
+        label: "points".to_string(),
+    });
+
+    let wrapped_score = Wrapper {
+        value: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+        label: "score".to_string(),
+    };
+    let mutable_score: MutableWrapper<PlayerScore> = wrapped_score.as_mutable_struct();
+    let mut points = mutable_score.value().points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(10));
+    mutable_score.update(Wrapper {
+        value: PlayerScore {
+            points: 20,
+            multiplier: 1.0
+        },
+        label: "score".to_string(),
+    });
+    assert_eq!(block_on(points.next()), Some(20));
+    assert_eq!(MutableWrapper::<u32>::default().snapshot(), Wrapper::default());
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Color {
+    Red,
+    Blue,
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Tagged<T> {
+    #[signal_basic] tag: T,
+}
+
+#[test]
+fn forces_basic_fields() {
+    let mutable_tagged = Tagged { tag: Color::Red }.as_mutable_struct();
+    let tag: &Mutable<Color> = &mutable_tagged.tag;
+    let mut tag_signal = mutable_tagged.tag_signal().to_stream();
+    assert_eq!(block_on(tag_signal.next()), Some(Color::Red));
+
+    tag.set(Color::Blue);
+    assert_eq!(block_on(tag_signal.next()), Some(Color::Blue));
+    assert_eq!(mutable_tagged.snapshot(), Tagged { tag: Color::Blue });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[signal_serde]
+#[signal_json]
+struct SavedScore {
+    points: u32,
+    tags: Vec<String>,
+}
+
+#[test]
+fn round_trips_through_serde() {
+    let mutable_score = SavedScore {
+        points: 10,
+        tags: vec!["new".to_string()],
+    }.as_mutable_struct();
+    mutable_score.points.set(12);
+
+    let json = serde_json::to_string(&mutable_score).unwrap();
+    assert_eq!(json, r#"{"points":12,"tags":["new"]}"#);
+    let restored: MutableSavedScore = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.snapshot(), mutable_score.snapshot());
+}
+
+#[test]
+fn converts_snapshots_to_json() {
+    let mutable_score = SavedScore {
+        points: 10,
+        tags: vec!["new".to_string()],
+    }.as_mutable_struct();
+    mutable_score.points.set(12);
+    assert_eq!(
+        mutable_score.snapshot_json(),
+        serde_json::json!({"points": 12, "tags": ["new"]})
+    );
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Address {
+    street: String,
+    city: String,
+}
+
+// The mutable struct keeps `street` and `city` in a nested `MutableAddress`, while
+// snapshots, and so serialized snapshots, have them inline.
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[signal_serde]
+struct Contact {
+    name: String,
+    #[signal_snapshot_flatten = "address: Address"] street: String,
+    #[signal_snapshot_flatten = "address: Address"] city: String,
+}
+
+#[test]
+fn flattens_nested_fields_in_snapshots() {
+    let contact = Contact {
+        name: "Ada".to_string(),
+        street: "1 Loop Rd".to_string(),
+        city: "London".to_string(),
+    };
+    let mutable_contact = contact.clone().as_mutable_struct();
+    let address: &MutableAddress = &mutable_contact.address;
+    assert_eq!(address.city.get_cloned(), "London");
+
+    address.city.set("Paris".to_string());
+    assert_eq!(mutable_contact.diff(&contact), vec![ContactFieldDiff::Address(vec![AddressFieldDiff::City])]);
+    assert_eq!(mutable_contact.snapshot(), Contact {
+        name: "Ada".to_string(),
+        street: "1 Loop Rd".to_string(),
+        city: "Paris".to_string(),
+    });
+    assert_eq!(
+        serde_json::to_string(&mutable_contact).unwrap(),
+        r#"{"name":"Ada","street":"1 Loop Rd","city":"Paris"}"#
+    );
+
+    mutable_contact.update(Contact {
+        name: "Bo".to_string(),
+        street: "2 Main St".to_string(),
+        city: "Rome".to_string(),
+    });
+    assert_eq!(mutable_contact.address.street.get_cloned(), "2 Main St");
+    assert_eq!(mutable_contact.address.city.get_cloned(), "Rome");
+    assert_eq!(
+        block_on(mutable_contact.snapshot_signal().to_stream().next()).map(|contact| contact.city),
+        Some("Rome".to_string())
+    );
+}
+
+#[test]
+fn updates_from_signal() {
+    let mutable_score = PlayerScore {
+        points: 0,
+        multiplier: 1.0
+    }.as_mutable_struct();
+    let points = mutable_score.points.clone();
+
+    let source = Mutable::new(PlayerScore {
+        points: 10,
+        multiplier: 1.0
+    });
+    let source_signal = source.signal_cloned();
+    source.set(PlayerScore {
+        points: 30,
+        multiplier: 3.0
+    });
+    drop(source);
+
+    block_on(mutable_score.update_from_signal(source_signal));
+    assert_eq!(points.get(), 30);
+}
+
+#[test]
+fn gets_vec_field_signal_vecs() {
+    let mutable_composed_struct = ComposedStruct {
+        score: PlayerScore {
+            points: 0,
+            multiplier: 1.0
+        },
+        events: vec!["Start".to_string()],
+    }.as_mutable_struct();
+    let mut events = mutable_composed_struct.events_signal_vec().to_stream();
+    assert_eq!(block_on(events.next()), Some(VecDiff::Replace {
+        values: vec!["Start".to_string()]
+    }));
+
+    mutable_composed_struct.events.lock_mut().push_cloned("First".to_string());
+    assert_eq!(block_on(events.next()), Some(VecDiff::Push {
+        value: "First".to_string()
+    }));
+    mutable_composed_struct.events.lock_mut().push_cloned("Second".to_string());
+    assert_eq!(block_on(events.next()), Some(VecDiff::Push {
+        value: "Second".to_string()
+    }));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Grid {
+    rows: Vec<Vec<i32>>,
+    #[mutable_type = "MutableStructVec<MutableVec<i32>>"]
+    observed_rows: Vec<Vec<i32>>,
+}
+
+#[test]
+fn round_trips_nested_vecs() {
+    let grid = Grid {
+        rows: vec![vec![1, 2], vec![3]],
+        observed_rows: vec![vec![4], vec![5, 6]],
+    };
+    let mutable_grid = grid.as_mutable_struct();
+    assert_eq!(mutable_grid.snapshot(), grid);
+    let mut first_row = mutable_grid.observed_rows.lock_ref()[0].signal_vec_cloned().to_stream();
+    assert_eq!(block_on(first_row.next()), Some(VecDiff::Replace { values: vec![4] }));
+
+    let updated = Grid {
+        rows: vec![vec![1, 2, 7]],
+        observed_rows: vec![vec![4, 8], vec![5, 6]],
+    };
+    mutable_grid.update(updated.clone());
+    assert_eq!(mutable_grid.snapshot(), updated);
+    assert_eq!(block_on(first_row.next()), Some(VecDiff::Replace { values: vec![4, 8] }));
+}
+
+#[test]
+fn replaces_and_returns_previous_snapshot() {
+    let composed_struct = ComposedStruct {
+        score: PlayerScore {
+            points: 40,
+            multiplier: 0.4
+        },
+        events: vec!["First".to_string()],
+    };
+    let mutable_composed_struct = composed_struct.as_mutable_struct();
+
+    let updated = ComposedStruct {
+        score: PlayerScore {
+            points: 50,
+            multiplier: 0.9
+        },
+        events: vec![],
+    };
+    let previous = mutable_composed_struct.replace_with(updated.clone());
+    assert_eq!(previous, composed_struct);
+    assert_eq!(mutable_composed_struct.snapshot(), updated);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_accessor_suffix = "_changes"]
+struct RenamedAccessors {
+    #[signal_accessor = "observe_points"] points: u32,
+    multiplier: f32,
+    events: Vec<String>,
+}
+
+#[test]
+fn renames_signal_accessors() {
+    let mutable_struct = RenamedAccessors {
+        points: 1,
+        multiplier: 2.0,
+        events: vec!["First".to_string()],
+    }.as_mutable_struct();
+    let mut points = mutable_struct.observe_points().to_stream();
+    let mut multiplier = mutable_struct.multiplier_changes_cloned().to_stream();
+    let mut events = mutable_struct.events_changes_vec().to_stream();
+    assert_eq!(block_on(points.next()), Some(1));
+    assert_eq!(block_on(multiplier.next()), Some(2.0));
+    assert_eq!(block_on(events.next()), Some(VecDiff::Replace {
+        values: vec!["First".to_string()]
+    }));
+}
+
+#[test]
+fn converts_with_from() {
+    let score = PlayerScore {
+        points: 3,
+        multiplier: 1.5
+    };
+    let mutable_score = MutablePlayerScore::from(score.clone());
+    mutable_score.points.set(4);
+
+    let updated: PlayerScore = (&mutable_score).into();
+    assert_eq!(updated, PlayerScore {
+        points: 4,
+        multiplier: 1.5
+    });
+    assert_eq!(PlayerScore::from(&MutablePlayerScore::from(score.clone())), score);
+}
+
+fn same_bits(a: &f32, b: &f32) -> bool {
+    a.to_bits() == b.to_bits()
+}
+
+#[derive(AsMutableStruct, Debug, Clone)]
+struct Measurement {
+    #[signal_eq_fn = "same_bits"] reading: f32,
+}
+
+#[test]
+fn compares_with_custom_eq_fn() {
+    let mutable_measurement = Measurement { reading: f32::NAN }.as_mutable_struct();
+    let mut reading = mutable_measurement.reading_signal().to_stream();
+    assert!(block_on(reading.next()).unwrap().is_nan());
+
+    mutable_measurement.update_changed(Measurement { reading: f32::NAN });
+    assert_eq!(reading.next().now_or_never(), None);
+
+    mutable_measurement.update_changed(Measurement { reading: 1.5 });
+    assert_eq!(block_on(reading.next()), Some(1.5));
+    mutable_measurement.update_changed(Measurement { reading: 1.5 });
+    assert_eq!(reading.next().now_or_never(), None);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Match {
+    winner: Box<PlayerScore>,
+    rounds: Box<u32>,
+}
+
+#[test]
+fn supports_boxed_fields() {
+    let game = Match {
+        winner: Box::new(PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        }),
+        rounds: Box::new(3),
+    };
+    let mutable_match = game.as_mutable_struct();
+    let winner: &MutablePlayerScore = mutable_match.winner();
+    let mut points = winner.points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(10));
+    assert_eq!(mutable_match.snapshot(), game);
+
+    let updated = Match {
+        winner: Box::new(PlayerScore {
+            points: 20,
+            multiplier: 1.0
+        }),
+        rounds: Box::new(4),
+    };
+    mutable_match.update(updated.clone());
+    assert_eq!(block_on(points.next()), Some(20));
+    assert_eq!(mutable_match.snapshot(), updated);
+    assert_eq!(*mutable_match.rounds.get_cloned(), 4);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Config {
+    shared: Arc<PlayerScore>,
+    motd: Arc<String>,
+}
+
+#[test]
+fn supports_shared_pointer_fields() {
+    let config = Config {
+        shared: Arc::new(PlayerScore {
+            points: 1,
+            multiplier: 1.0
+        }),
+        motd: Arc::new("Welcome".to_string()),
+    };
+    let mutable_config = config.as_mutable_struct();
+    let motd: &Mutable<Arc<String>> = &mutable_config.motd;
+    assert_eq!(**motd.lock_ref(), "Welcome");
+
+    let shared_score: Arc<MutablePlayerScore> = mutable_config.shared().clone();
+    shared_score.points.set(2);
+    let snapshot = mutable_config.snapshot();
+    assert_eq!(snapshot.shared.points, 2);
+    assert!(Arc::ptr_eq(&snapshot.motd, &mutable_config.motd.get_cloned()));
+
+    let updated = Config {
+        shared: Arc::new(PlayerScore {
+            points: 3,
+            multiplier: 1.0
+        }),
+        motd: Arc::new("Goodbye".to_string()),
+    };
+    mutable_config.update(updated.clone());
+    assert_eq!(shared_score.points.get(), 3);
+    assert_eq!(mutable_config.snapshot(), updated);
+}
+
+#[test]
+fn observes_snapshots() {
+    let mutable_score = PlayerScore {
+        points: 1,
+        multiplier: 1.0
+    }.as_mutable_struct();
+    let observed = Rc::new(RefCell::new(vec![]));
+    let changes = Rc::new(RefCell::new(vec![]));
+
+    let observed_clone = observed.clone();
+    let mut observer = Box::pin(mutable_score.observe(move |snapshot: PlayerScore| {
+        observed_clone.borrow_mut().push(snapshot.points);
+    }));
+    let changes_clone = changes.clone();
+    let mut change_observer = Box::pin(mutable_score.observe_changes(
+        move |previous: Option<PlayerScore>, snapshot: PlayerScore| {
+            changes_clone
+                .borrow_mut()
+                .push((previous.map(|previous| previous.points), snapshot.points));
+        },
+    ));
+    assert_eq!((&mut observer).now_or_never(), None);
+    assert_eq!((&mut change_observer).now_or_never(), None);
+
+    mutable_score.points.set(2);
+    assert_eq!((&mut observer).now_or_never(), None);
+    assert_eq!((&mut change_observer).now_or_never(), None);
+    mutable_score.points.set(3);
+    drop(mutable_score);
+    block_on(observer);
+    block_on(change_observer);
+
+    assert_eq!(*observed.borrow(), vec![1, 2, 3]);
+    assert_eq!(*changes.borrow(), vec![(None, 1), (Some(1), 2), (Some(2), 3)]);
+}
+
+#[test]
+fn clones_without_cloning_the_snapshot() {
+    let mutable_struct = CustomNamedStruct { level: 3 }.as_mutable_struct();
+    let cloned = mutable_struct.clone();
+    cloned.level.set(4);
+    assert_eq!(mutable_struct.snapshot(), CustomNamedStruct { level: 3 });
+    assert_eq!(cloned.snapshot(), CustomNamedStruct { level: 4 });
+}
+
+/// A struct whose attributes are meant for other derives.
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForeignAttributes {
+    /// Documented field.
+    #[serde(rename = "pts")]
+    total_points: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    recent_events: Vec<String>,
+}
+
+#[test]
+fn ignores_foreign_attributes() {
+    let snapshot: ForeignAttributes = serde_json::from_str(r#"{"pts":5}"#).unwrap();
+    let mutable_struct = snapshot.as_mutable_struct();
+    mutable_struct.total_points.set(6);
+    assert_eq!(
+        serde_json::to_string(&mutable_struct.snapshot()).unwrap(),
+        r#"{"pts":6}"#
+    );
+}
+
+#[test]
+fn takes_consistent_snapshots() {
+    let mutable_score = Arc::new(PlayerScore {
+        points: 0,
+        multiplier: 0.0
+    }.as_mutable_struct());
+    let writers = (0..4)
+        .map(|writer| {
+            let mutable_score = mutable_score.clone();
+            std::thread::spawn(move || {
+                for i in 0..1000 {
+                    let points = writer * 1000 + i;
+                    mutable_score.consistent_update(PlayerScore {
+                        points,
+                        multiplier: points as f32 * 2.0,
+                    });
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..1000 {
+        let snapshot = mutable_score.consistent_snapshot();
+        assert_eq!(snapshot.multiplier, snapshot.points as f32 * 2.0);
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+}
+
+#[test]
+fn reads_fields_through_one_guard() {
+    let mutable_score = Arc::new(MutablePlayerScore::new(0, 0.0));
+    let writers = (0..4)
+        .map(|writer| {
+            let mutable_score = mutable_score.clone();
+            std::thread::spawn(move || {
+                for i in 0..1000 {
+                    let points = writer * 1000 + i;
+                    mutable_score.consistent_update(PlayerScore {
+                        points,
+                        multiplier: points as f32 * 2.0,
+                    });
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..1000 {
+        let score: PlayerScoreReadGuard = mutable_score.read();
+        assert_eq!(*score.multiplier, *score.points as f32 * 2.0);
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Default)]
+struct Marker;
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone, Default)]
+struct EmptyStruct {}
+
+#[test]
+fn supports_structs_without_fields() {
+    let mutable_marker: MutableMarker = Marker.as_mutable_struct();
+    assert_eq!(MutableMarker, mutable_marker);
+    mutable_marker.update(Marker);
+    assert_eq!(mutable_marker.snapshot(), Marker);
+    assert_eq!(block_on(mutable_marker.snapshot_signal().to_stream().next()), Some(Marker));
+
+    let mutable_empty = EmptyStruct {}.as_mutable_struct();
+    mutable_empty.update(EmptyStruct {});
+    assert_eq!(mutable_empty.snapshot(), EmptyStruct {});
+    assert_eq!(MutableEmptyStruct::default().snapshot(), EmptyStruct::default());
+}
+
+#[test]
+fn diffs_against_snapshots() {
+    let character = Character {
+        level: 1,
+        name: "Ada".to_string(),
+        best_score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+    };
+    let mutable_character = character.clone().as_mutable_struct();
+    assert_eq!(mutable_character.diff(&character), vec![]);
+
+    mutable_character.level.set(2);
+    mutable_character.best_score.points.set(20);
+    assert_eq!(mutable_character.diff(&character), vec![
+        CharacterFieldDiff::Level,
+        CharacterFieldDiff::BestScore(vec![PlayerScoreFieldDiff::Points]),
+    ]);
+}
+
+#[test]
+fn sets_fields_by_name() {
+    let character = Character {
+        level: 1,
+        name: "Ada".to_string(),
+        best_score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+    }
+    .as_mutable_struct();
+    let new_score = PlayerScore {
+        points: 20,
+        multiplier: 2.0,
+    };
+    assert_eq!(character.set_field(CharacterField::Level, CharacterFieldValue::Level(2)), Ok(()));
+    assert_eq!(
+        character.set_field(CharacterField::Name, CharacterFieldValue::Name("Grace".to_string())),
+        Ok(())
+    );
+    assert_eq!(
+        character.set_field(CharacterField::BestScore, CharacterFieldValue::BestScore(new_score.clone())),
+        Ok(())
+    );
+    assert_eq!(
+        character.set_field(CharacterField::Name, CharacterFieldValue::Level(3)),
+        Err(CharacterFieldValue::Level(3))
+    );
+    assert_eq!(CharacterFieldValue::Level(3).field(), CharacterField::Level);
+    assert_eq!(character.snapshot(), Character {
+        level: 2,
+        name: "Grace".to_string(),
+        best_score: new_score,
+    });
+}
+
+#[test]
+fn applies_partial_patches() {
+    let character = Character {
+        level: 1,
+        name: "Ada".to_string(),
+        best_score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+    }
+    .as_mutable_struct();
+    character.apply_diff(ApplyableCharacter {
+        level: Some(2),
+        ..Default::default()
+    });
+    character.apply_diff(ApplyableCharacter {
+        best_score: Some(ApplyablePlayerScore {
+            points: Some(20),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    assert_eq!(character.snapshot(), Character {
+        level: 2,
+        name: "Ada".to_string(),
+        best_score: PlayerScore {
+            points: 20,
+            multiplier: 1.0
+        },
+    });
+}
+
+mod scoring {
+    use futures_signals_structs::AsMutableStruct;
+
+    #[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+    pub struct Bonus {
+        pub points: u32,
+    }
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Round {
+    bonus: crate::scoring::Bonus,
+    score: player_score,
+    #[mutable_type = "scoring::MutableBonus"] penalty: scoring::Bonus,
+}
+
+#[test]
+fn nests_qualified_paths_and_aliases() {
+    let round = Round {
+        bonus: scoring::Bonus { points: 5 },
+        score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+        penalty: scoring::Bonus { points: 2 },
+    };
+    let mutable_round = round.clone().as_mutable_struct();
+    mutable_round.bonus.points.set(6);
+    mutable_round.score.points.set(11);
+    mutable_round.penalty.points.set(3);
+    assert_eq!(mutable_round.snapshot(), Round {
+        bonus: scoring::Bonus { points: 6 },
+        score: PlayerScore {
+            points: 11,
+            multiplier: 1.0
+        },
+        penalty: scoring::Bonus { points: 3 },
+    });
+}
+
+#[test]
+fn freezes_into_read_only_handles() {
+    let mutable_score = Arc::new(PlayerScore {
+        points: 10,
+        multiplier: 1.0
+    }
+    .as_mutable_struct());
+    let read_only_score = ReadOnlyMutablePlayerScore::from(mutable_score.clone());
+    let mut points = read_only_score.points_signal().to_stream();
+    assert_eq!(block_on(points.next()), Some(10));
+
+    mutable_score.points.set(20);
+    assert_eq!(block_on(points.next()), Some(20));
+    assert_eq!(read_only_score.clone().snapshot(), PlayerScore {
+        points: 20,
+        multiplier: 1.0
+    });
+
+    let frozen_score = PlayerScore {
+        points: 5,
+        multiplier: 2.0
+    }
+    .as_mutable_struct()
+    .freeze();
+    assert_eq!(block_on(frozen_score.multiplier_signal().to_stream().next()), Some(2.0));
+    assert_eq!(block_on(frozen_score.snapshot_signal().to_stream().next()), Some(PlayerScore {
+        points: 5,
+        multiplier: 2.0
+    }));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Board {
+    cells: [u8; 9],
+    players: [String; 2],
+}
+
+#[test]
+fn stores_arrays_as_basic_fields() {
+    let board = Board {
+        cells: [0; 9],
+        players: ["Ada".to_string(), "Grace".to_string()],
+    };
+    let mutable_board = board.as_mutable_struct();
+    let mut cells = mutable_board.cells_signal().to_stream();
+    assert_eq!(block_on(cells.next()), Some([0; 9]));
+
+    mutable_board.cells.lock_mut()[4] = 1;
+    assert_eq!(block_on(cells.next()), Some([0, 0, 0, 0, 1, 0, 0, 0, 0]));
+
+    mutable_board.update(Board {
+        cells: [2; 9],
+        players: ["Alan".to_string(), "Grace".to_string()],
+    });
+    assert_eq!(mutable_board.snapshot(), Board {
+        cells: [2; 9],
+        players: ["Alan".to_string(), "Grace".to_string()],
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Permissions {
+    roles: HashSet<String>,
+    levels: BTreeSet<u8>,
+}
+
+#[test]
+fn round_trips_sets() {
+    let permissions = Permissions {
+        roles: vec!["reader".to_string()].into_iter().collect(),
+        levels: vec![1, 2].into_iter().collect(),
+    };
+    let mutable_permissions = permissions.as_mutable_struct();
+    let mut roles = mutable_permissions.roles.signal_cloned().to_stream();
+    assert_eq!(block_on(roles.next()).map(|roles| roles.len()), Some(1));
+
+    mutable_permissions.roles.lock_mut().insert("writer".to_string());
+    assert_eq!(block_on(roles.next()).map(|roles| roles.len()), Some(2));
+    mutable_permissions.levels.lock_mut().remove(&1);
+    mutable_permissions.levels.lock_mut().insert(3);
+    assert_eq!(mutable_permissions.snapshot(), Permissions {
+        roles: vec!["reader".to_string(), "writer".to_string()].into_iter().collect(),
+        levels: vec![2, 3].into_iter().collect(),
+    });
+
+    mutable_permissions.update(Permissions {
+        roles: HashSet::new(),
+        levels: BTreeSet::new(),
+    });
+    assert!(mutable_permissions.snapshot().roles.is_empty());
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct JobQueue {
+    jobs: VecDeque<u32>,
+}
+
+#[test]
+fn round_trips_vec_deques() {
+    let queue = JobQueue {
+        jobs: vec![2, 3].into_iter().collect(),
+    };
+    let mutable_queue = queue.as_mutable_struct();
+    mutable_queue.jobs.push_front(1);
+    mutable_queue.jobs.push_back(4);
+    assert_eq!(mutable_queue.snapshot(), JobQueue {
+        jobs: vec![1, 2, 3, 4].into_iter().collect(),
+    });
+    assert_eq!(mutable_queue.jobs.lock_ref().as_slice(), &[1, 2, 3, 4]);
+
+    assert_eq!(mutable_queue.jobs.pop_front(), Some(1));
+    assert_eq!(mutable_queue.jobs.pop_back(), Some(4));
+    let mut snapshots = mutable_queue.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()), Some(JobQueue {
+        jobs: vec![2, 3].into_iter().collect(),
+    }));
+
+    mutable_queue.update(JobQueue {
+        jobs: vec![5].into_iter().collect(),
+    });
+    assert_eq!(block_on(snapshots.next()), Some(JobQueue {
+        jobs: vec![5].into_iter().collect(),
+    }));
+}
+
+#[test]
+fn overrides_mutable_visibility() {
+    let settings = settings::default_settings();
+    settings.volume.set(7);
+    assert_eq!(settings.volume.get(), 7);
+    assert_eq!(block_on(settings.volume_signal().to_stream().next()), Some(7));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_hash]
+struct Coordinate {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+#[allow(clippy::mutable_key_type)]
+fn hashes_current_values() {
+    let mut coordinates = HashSet::new();
+    coordinates.insert(Coordinate { x: 1, y: 2 }.as_mutable_struct());
+    coordinates.insert(Coordinate { x: 1, y: 2 }.as_mutable_struct());
+    coordinates.insert(Coordinate { x: 2, y: 1 }.as_mutable_struct());
+    assert_eq!(coordinates.len(), 2);
+
+    let moved = Coordinate { x: 0, y: 0 }.as_mutable_struct();
+    moved.x.set(2);
+    moved.y.set(1);
+    assert!(coordinates.contains(&moved));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct HighScores {
+    #[mutable_type = "MutableVec<MutablePlayerScore>"] scores: Vec<PlayerScore>,
+}
+
+#[test]
+fn stores_vecs_of_mutable_structs() {
+    let high_scores = HighScores {
+        scores: vec![
+            PlayerScore {
+                points: 10,
+                multiplier: 1.0
+            },
+            PlayerScore {
+                points: 5,
+                multiplier: 2.0
+            },
+        ],
+    };
+    let mutable_high_scores = high_scores.clone().as_mutable_struct();
+    mutable_high_scores.scores.lock_ref()[1].points.set(6);
+    assert_eq!(mutable_high_scores.snapshot().scores[1], PlayerScore {
+        points: 6,
+        multiplier: 2.0
+    });
+    assert_eq!(mutable_high_scores.diff(&high_scores), vec![HighScoresFieldDiff::Scores(vec![1])]);
+
+    let mut snapshots = mutable_high_scores.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()).map(|snapshot| snapshot.scores.len()), Some(2));
+    mutable_high_scores.update(HighScores {
+        scores: vec![PlayerScore {
+            points: 1,
+            multiplier: 1.0
+        }],
+    });
+    assert_eq!(block_on(snapshots.next()), Some(HighScores {
+        scores: vec![PlayerScore {
+            points: 1,
+            multiplier: 1.0
+        }],
+    }));
+    mutable_high_scores.update_changed(high_scores.clone());
+    assert_eq!(mutable_high_scores.snapshot(), high_scores);
+}
+
+fn validate_range(range: &Range) -> Result<(), ValidationError> {
+    if range.min > range.max {
+        return Err(ValidationError::new("min must not exceed max"));
+    }
+    Ok(())
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_validate = "validate_range"]
+struct Range {
+    min: u32,
+    max: u32,
+}
+
+#[test]
+fn validates_before_updating() {
+    let mutable_range = Range { min: 1, max: 5 }.as_mutable_struct();
+    assert_eq!(mutable_range.try_update(Range { min: 2, max: 8 }), Ok(()));
+    assert_eq!(mutable_range.snapshot(), Range { min: 2, max: 8 });
+
+    let error = mutable_range.try_update(Range { min: 9, max: 3 }).unwrap_err();
+    assert_eq!(error.message(), "min must not exceed max");
+    assert_eq!(mutable_range.snapshot(), Range { min: 2, max: 8 });
+}
+
+// Trees are boxed on purpose, to cover recursion through the `Box` impls.
+#[allow(clippy::vec_box)]
+mod tree {
+    use futures_signals_structs::{AsMutableStruct, MutableStructVec};
+
+    #[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+    pub struct Node {
+        pub value: i32,
+        #[mutable_type = "MutableStructVec<Box<MutableNode>>"] pub children: Vec<Box<Node>>,
+    }
+
+    #[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+    pub struct PlainNode {
+        pub value: i32,
+        pub children: Vec<Box<PlainNode>>,
+    }
+}
+
+use tree::{Node, PlainNode};
+
+#[test]
+fn supports_recursive_structs() {
+    let leaf = |value| Box::new(Node {
+        value,
+        children: vec![],
+    });
+    let tree = Node {
+        value: 1,
+        children: vec![Box::new(Node {
+            value: 2,
+            children: vec![leaf(3), leaf(4)],
+        })],
+    };
+    let mutable_tree = tree.as_mutable_struct();
+    let mut snapshots = mutable_tree.snapshot_signal().to_stream();
+    assert_eq!(block_on(snapshots.next()).map(|snapshot| snapshot.children[0].children[1].value), Some(4));
+
+    mutable_tree.children.lock_ref()[0].children.lock_ref()[1].value.set(5);
+    assert_eq!(block_on(snapshots.next()).map(|snapshot| snapshot.children[0].children[1].value), Some(5));
+    assert_eq!(mutable_tree.snapshot(), Node {
+        value: 1,
+        children: vec![Box::new(Node {
+            value: 2,
+            children: vec![leaf(3), leaf(5)],
+        })],
+    });
+
+    let plain_tree = PlainNode {
+        value: 1,
+        children: vec![Box::new(PlainNode {
+            value: 2,
+            children: vec![],
+        })],
+    }
+    .as_mutable_struct();
+    plain_tree.children.lock_mut().set_cloned(0, Box::new(PlainNode {
+        value: 3,
+        children: vec![],
+    }));
+    assert_eq!(plain_tree.snapshot().children[0].value, 3);
+    assert_eq!(plain_tree, plain_tree.clone());
+}
+
+#[derive(AsMutableStruct, Clone)]
+#[mutable_derive(Debug, Clone)]
+struct Forwarded {
+    count: u32,
+}
+
+#[test]
+fn forwards_derives_to_mutable_structs() {
+    let mutable_forwarded = Forwarded { count: 3 }.as_mutable_struct();
+    assert_eq!(
+        format!("{:?}", mutable_forwarded),
+        format!("MutableForwarded {{ count: {:?} }}", mutable_forwarded.count)
+    );
+
+    // A derived Clone shares the underlying Mutable.
+    let shared = mutable_forwarded.clone();
+    shared.count.set(4);
+    assert_eq!(mutable_forwarded.count.get(), 4);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Cooldown {
+    label: Cow<'static, str>,
+    remaining: Duration,
+}
+
+#[test]
+fn treats_known_std_types_as_basic() {
+    let cooldown = Cooldown {
+        label: Cow::Borrowed("dash"),
+        remaining: Duration::from_secs(3),
+    }
+    .as_mutable_struct();
+    let label: &Mutable<Cow<'static, str>> = &cooldown.label;
+    label.set(Cow::Owned("dash ready".to_string()));
+    cooldown.remaining.set(Duration::from_secs(0));
+    assert_eq!(
+        cooldown.remaining_signal().to_stream().next().now_or_never(),
+        Some(Some(Duration::from_secs(0)))
+    );
+    assert_eq!(cooldown.snapshot(), Cooldown {
+        label: Cow::Borrowed("dash ready"),
+        remaining: Duration::from_secs(0),
+    });
+}
+
+#[test]
+fn waits_for_field_predicates() {
+    let score = Arc::new(PlayerScore {
+        points: 0,
+        multiplier: 1.0,
+    }
+    .as_mutable_struct());
+    let reached_goal = score.wait_for_points(|points| *points >= 100);
+
+    let setter = score.clone();
+    let handle = std::thread::spawn(move || {
+        for points in (0..=100).step_by(25) {
+            setter.points.set(points);
+        }
+    });
+    assert_eq!(block_on(reached_goal), Some(100));
+    handle.join().unwrap();
+
+    let never_reached = score.wait_for_points(|points| *points > 100);
+    drop(score);
+    assert_eq!(block_on(never_reached), None);
+}
+
+#[test]
+fn shares_mutable_structs_in_arcs() {
+    let score = PlayerScore {
+        points: 1,
+        multiplier: 1.0,
+    }
+    .as_mutable_struct()
+    .into_shared();
+    let other_handle = Arc::clone(&score);
+    other_handle.points.set(2);
+    score.multiplier.set(2.0);
+    assert_eq!(score.snapshot(), other_handle.snapshot());
+    assert_eq!(*other_handle.snapshot(), PlayerScore {
+        points: 2,
+        multiplier: 2.0,
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Sprite {
+    position: (f32, f32),
+    tint: (u8, u8, u8),
+}
+
+#[test]
+fn stores_tuples_as_basic_fields() {
+    let sprite = Sprite {
+        position: (0.0, 0.0),
+        tint: (255, 255, 255),
+    }
+    .as_mutable_struct();
+    let position: &Mutable<(f32, f32)> = &sprite.position;
+    position.set((1.5, -2.0));
+    sprite.tint.lock_mut().1 = 0;
+    assert_eq!(sprite.snapshot(), Sprite {
+        position: (1.5, -2.0),
+        tint: (255, 0, 255),
+    });
+}
+
+#[test]
+fn selects_field_signals_by_marker() {
+    fn first_value<M: GetFieldSignal<F>, F>(mutable: &M, field: F) -> Option<M::Value> {
+        block_on(Box::pin(mutable.get_field_signal(field).to_stream()).next())
+    }
+
+    let character = Character {
+        level: 1,
+        name: "Ada".to_string(),
+        best_score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+    }
+    .as_mutable_struct();
+    character.level.set(2);
+    character.best_score.points.set(20);
+    assert_eq!(first_value(&character, character_fields::Level), Some(2));
+    assert_eq!(first_value(&character, character_fields::Name), Some("Ada".to_string()));
+    assert_eq!(first_value(&character, character_fields::BestScore), Some(PlayerScore {
+        points: 20,
+        multiplier: 1.0
+    }));
+}
+
+// Its `score` marker lives in `player_fields`, so it doesn't collide with the
+// `PlayerScoreField` enum generated for `PlayerScore`.
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Player {
+    score: u32,
+}
+
+#[test]
+fn namespaces_field_markers() {
+    let player = MutablePlayer::new(3);
+    let player_score = MutablePlayerScore::new(40, 1.5);
+    player.set_field(PlayerField::Score, PlayerFieldValue::Score(4)).unwrap();
+    player_score.set_field(PlayerScoreField::Points, PlayerScoreFieldValue::Points(50)).unwrap();
+    let mut scores = Box::pin(player.get_field_signal(player_fields::Score).to_stream());
+    let mut points = Box::pin(player_score.get_field_signal(player_score_fields::Points).to_stream());
+    assert_eq!(block_on(scores.next()), Some(4));
+    assert_eq!(block_on(points.next()), Some(50));
+}
+
+/// A piece of equipment.
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Equipment {
+    /// How worn out the equipment is, from 0 to 100.
+    wear: u8,
+    /// The bonus granted while it is equipped.
+    #[doc = "Applies to every round."]
+    bonus: scoring::Bonus,
+}
+
+#[test]
+fn keeps_doc_comments() {
+    let equipment = Equipment {
+        wear: 0,
+        bonus: scoring::Bonus { points: 5 },
+    }
+    .as_mutable_struct();
+    equipment.wear.set(10);
+    assert_eq!(equipment.snapshot(), Equipment {
+        wear: 10,
+        bonus: scoring::Bonus { points: 5 },
+    });
+}
+
+#[test]
+fn snapshots_into_existing_buffers() {
+    let character = Character {
+        level: 1,
+        name: "Ada Lovelace".to_string(),
+        best_score: PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        },
+    }
+    .as_mutable_struct();
+    let mut snapshot = character.snapshot();
+    let name_buffer = snapshot.name.as_ptr();
+
+    for level in 2..10 {
+        character.level.set(level);
+        character.name.set(format!("Ada {}", level));
+        character.best_score.points.set(level as u32 * 10);
+        character.snapshot_into(&mut snapshot);
+        assert_eq!(snapshot, character.snapshot());
+        // The new names fit into the existing buffer, so it is reused.
+        assert_eq!(snapshot.name.as_ptr(), name_buffer);
+    }
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[allow(non_snake_case)]
+struct Token {
+    r#type: u32,
+    r#match: scoring::Bonus,
+    HTTPStatus: u16,
+}
+
+#[test]
+fn supports_raw_identifiers() {
+    let token = Token {
+        r#type: 1,
+        r#match: scoring::Bonus { points: 2 },
+        HTTPStatus: 200,
+    }
+    .as_mutable_struct();
+    token.r#type.set(3);
+    token.r#match().points.set(4);
+    token.HTTPStatus.set(404);
+    assert_eq!(token.type_signal().to_stream().next().now_or_never(), Some(Some(3)));
+    assert_eq!(token.snapshot(), Token {
+        r#type: 3,
+        r#match: scoring::Bonus { points: 4 },
+        HTTPStatus: 404,
+    });
+}
+
+#[test]
+fn groups_mutable_options() {
+    let display: settings::LiveDisplay = settings::default_display();
+    display.brightness.set(40);
+    assert_eq!(display.brightness.get(), 40);
+    assert_eq!(
+        format!("{:?}", display),
+        format!("LiveDisplay {{ brightness: {:?} }}", display.brightness)
+    );
+}
+
+#[test]
+fn stores_fields_as_another_type() {
+    let label = Label {
+        text: Cow::Borrowed("Ready"),
+        color: 0,
+    };
+    let mutable_label = label.clone().as_mutable_struct();
+    let text: &Mutable<String> = &mutable_label.text;
+    assert_eq!(text.get_cloned(), "Ready");
+    assert_eq!(mutable_label.diff(&label), vec![]);
+
+    text.set("Game over".to_string());
+    assert_eq!(mutable_label.diff(&label), vec![LabelFieldDiff::Text]);
+    let snapshot: Label = mutable_label.snapshot();
+    assert_eq!(snapshot.text, Cow::<str>::Owned("Game over".to_string()));
+
+    mutable_label.update(Label {
+        text: Cow::Borrowed("Paused"),
+        color: 1,
+    });
+    assert_eq!(
+        block_on(mutable_label.snapshot_signal().to_stream().next()).map(|label| label.text),
+        Some(Cow::Borrowed("Paused"))
+    );
+}
+
+// Snapshots are `OwnedView`, a generated copy of `View` that stores `name` as a `String`
+// and `scores` as a `Vec<u32>`.
+#[derive(AsMutableStruct)]
+struct View<'a> {
+    #[signal_store_as = "String"] name: &'a str,
+    #[signal_store_as = "Vec<u32>"] scores: &'a [u32],
+    level: u8,
+}
+
+#[test]
+fn stores_references_as_owned_values() {
+    let scores = [3, 5];
+    let view = View {
+        name: "Ada",
+        scores: &scores,
+        level: 1,
+    };
+    let mutable_view: MutableView = OwnedView::from(view).as_mutable_struct();
+    let name: &Mutable<String> = &mutable_view.name;
+    assert_eq!(name.get_cloned(), "Ada");
+
+    let borrowed_name = String::from("Grace");
+    mutable_view.update(View {
+        name: &borrowed_name,
+        scores: &scores[1..],
+        level: 2,
+    }
+    .into());
+    drop(borrowed_name);
+    assert_eq!(mutable_view.snapshot(), OwnedView {
+        name: "Grace".to_string(),
+        scores: vec![5],
+        level: 2,
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Inventory {
+    #[signal_store_as = "u32"]
+    #[signal_update_with = "parse_count"]
+    #[signal_snapshot_with = "format_count"]
+    count: String,
+}
+
+fn parse_count(count: String) -> u32 {
+    count.trim_end_matches(" items").parse().unwrap_or(0)
+}
+
+fn format_count(count: &u32) -> String {
+    format!("{} items", count)
+}
+
+mod inventory {
+    use futures_signals_structs::AsMutableStruct;
+
+    pub mod items {
+        use futures_signals_structs::AsMutableStruct;
+
+        #[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+        pub struct Item {
+            pub(crate) name: String,
+            pub(super) count: u32,
+            pub(in crate::inventory) weight: f32,
+        }
+    }
+
+    pub fn rope() -> items::MutableItem {
+        items::Item {
+            name: "Rope".to_string(),
+            count: 3,
+            weight: 0.5,
+        }
+        .as_mutable_struct()
+    }
+
+    pub fn total_weight(item: &items::MutableItem) -> f32 {
+        item.count.get() as f32 * item.weight.get()
+    }
+}
+
+#[test]
+fn converts_fields_with_custom_functions() {
+    let inventory = Inventory {
+        count: "3 items".to_string(),
+    };
+    let mutable_inventory = inventory.clone().as_mutable_struct();
+    let count: &Mutable<u32> = &mutable_inventory.count;
+    assert_eq!(count.get(), 3);
+    assert_eq!(mutable_inventory.diff(&inventory), vec![]);
+
+    count.set(count.get() + 2);
+    assert_eq!(mutable_inventory.snapshot().count, "5 items");
+    let previous = mutable_inventory.replace_with(Inventory {
+        count: "8 items".to_string(),
+    });
+    assert_eq!(previous.count, "5 items");
+    assert_eq!(count.get(), 8);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_key = "id"]
+struct Entity {
+    id: u32,
+    health: u32,
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct World {
+    #[mutable_type = "MutableKeyedVec<MutableEntity>"]
+    entities: Vec<Entity>,
+}
+
+#[test]
+fn keeps_keyed_elements_across_reorders() {
+    let world = World {
+        entities: vec![Entity { id: 1, health: 10 }, Entity { id: 2, health: 20 }],
+    }
+    .as_mutable_struct();
+    let first_entity = world.entities.lock_ref()[0].clone();
+    let mut first_health = first_entity.health_signal().to_stream();
+    assert_eq!(block_on(first_health.next()), Some(10));
+
+    world.update(World {
+        entities: vec![
+            Entity { id: 3, health: 30 },
+            Entity { id: 2, health: 20 },
+            Entity { id: 1, health: 5 },
+        ],
+    });
+    assert_eq!(block_on(first_health.next()), Some(5));
+    assert!(Arc::ptr_eq(&world.entities.lock_ref()[2], &first_entity));
+    let ids = world.snapshot().entities.iter().map(|entity| entity.id).collect::<Vec<u32>>();
+    assert_eq!(ids, vec![3, 2, 1]);
+
+    world.update(World {
+        entities: vec![Entity { id: 2, health: 20 }],
+    });
+    first_entity.health.set(0);
+    assert_eq!(world.snapshot(), World {
+        entities: vec![Entity { id: 2, health: 20 }],
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct League {
+    #[mutable_type = "MutableStructMap<String, MutablePlayerScore>"]
+    scores: BTreeMap<String, PlayerScore>,
+}
+
+#[test]
+fn observes_fields_of_map_entries() {
+    let score = |points| PlayerScore { points, multiplier: 1.0 };
+    let league = League {
+        scores: vec![("ada".to_string(), score(10)), ("bo".to_string(), score(20))]
+            .into_iter()
+            .collect(),
+    }
+    .as_mutable_struct();
+    let ada = (*league.scores.lock_ref())["ada"].clone();
+    let mut ada_points = ada.points_signal().to_stream();
+    let mut snapshots = league.snapshot_stream();
+    assert_eq!(block_on(ada_points.next()), Some(10));
+    assert_eq!(block_on(snapshots.next()).unwrap().scores["ada"], score(10));
+
+    ada.points.set(15);
+    assert_eq!(block_on(snapshots.next()).unwrap().scores["ada"], score(15));
+
+    league.update(League {
+        scores: vec![("ada".to_string(), score(30)), ("cy".to_string(), score(40))]
+            .into_iter()
+            .collect(),
+    });
+    assert_eq!(block_on(ada_points.next()), Some(30));
+    assert!(Arc::ptr_eq(&(*league.scores.lock_ref())["ada"], &ada));
+    let names = league.snapshot().scores.into_keys().collect::<Vec<String>>();
+    assert_eq!(names, vec!["ada", "cy"]);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct RankedScore {
+    rank: u32,
+    #[signal_flatten] score: PlayerScore,
+}
+
+#[test]
+fn flattens_nested_fields() {
+    let ranked_score = RankedScore {
+        rank: 1,
+        score: PlayerScore {
+            points: 10,
+            multiplier: 1.0,
+        },
+    }
+    .as_mutable_struct();
+    ranked_score.points.set(20);
+    ranked_score.rank.set(2);
+    assert_eq!(block_on(ranked_score.points_signal().to_stream().next()), Some(20));
+    assert_eq!(ranked_score.snapshot(), RankedScore {
+        rank: 2,
+        score: PlayerScore {
+            points: 20,
+            multiplier: 1.0,
+        },
+    });
+}
+
+#[derive(AsMutableStruct, Clone)]
+struct Button {
+    label: String,
+    on_click: Arc<dyn Fn() -> i32>,
+    #[signal_skip] on_hover: Arc<dyn Fn() -> i32>,
+}
+
+#[test]
+fn stores_trait_objects_as_basic_fields() {
+    let button = Button {
+        label: "Play".to_string(),
+        on_click: Arc::new(|| 1),
+        on_hover: Arc::new(|| 2),
+    }
+    .as_mutable_struct();
+    button.on_click.set(Arc::new(|| 3));
+    let snapshot = button.snapshot();
+    assert_eq!(snapshot.label, "Play");
+    assert_eq!((snapshot.on_click)(), 3);
+    assert_eq!((snapshot.on_hover)(), 2);
+}
+
+// Snapshots can't clone `on_press`, so only the mutable struct, its constructor and its
+// accessors are generated for it.
+#[derive(AsMutableStruct)]
+struct PressableButton {
+    label: String,
+    #[signal_skip] on_press: Box<dyn Fn() -> i32>,
+}
+
+#[test]
+fn skips_fields_that_are_not_clone() {
+    let button = MutablePressableButton::new("Play".to_string(), Box::new(|| 7));
+    let mut labels = button.label_signal_cloned().to_stream();
+    button.set_label("Pause".to_string());
+    assert_eq!(block_on(labels.next()), Some("Pause".to_string()));
+    assert_eq!(button.label(), "Pause");
+    assert_eq!((button.on_press)(), 7);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Telemetry {
+    frames: u32,
+    #[cfg(feature = "serde")] serialized_bytes: usize,
+    #[cfg(not(feature = "serde"))] stats: PlayerScore,
+}
+
+#[test]
+fn omits_configured_out_fields() {
+    #[cfg(feature = "serde")]
+    let telemetry = MutableTelemetry::new(1, 100);
+    #[cfg(not(feature = "serde"))]
+    let telemetry = MutableTelemetry::new(1, PlayerScore {
+        points: 10,
+        multiplier: 1.0,
+    });
+    telemetry.frames.set(2);
+    #[cfg(feature = "serde")]
+    {
+        telemetry.serialized_bytes.set(200);
+        assert_eq!(telemetry.snapshot(), Telemetry { frames: 2, serialized_bytes: 200 });
+        assert_eq!(MutableTelemetry::field_names(), ["frames", "serialized_bytes"]);
+    }
+    #[cfg(not(feature = "serde"))]
+    {
+        telemetry.stats.points.set(20);
+        assert_eq!(telemetry.snapshot(), Telemetry {
+            frames: 2,
+            stats: PlayerScore {
+                points: 20,
+                multiplier: 1.0,
+            },
+        });
+        assert_eq!(MutableTelemetry::field_names(), ["frames", "stats"]);
+    }
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_transparent]
+struct Meters(f32);
+
+#[test]
+fn forwards_transparent_newtypes() {
+    let meters = Meters(1.0).as_mutable_struct();
+    meters.set(3.0);
+    assert_eq!(meters.0.get(), 3.0);
+    assert_eq!(meters.snapshot(), Meters(3.0));
+}
+
+mod renamed {
+    pub(crate) use futures_signals as signals;
+    pub(crate) use futures_signals_structs_traits as traits;
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_crate(traits = "crate::renamed::traits", signals = "crate::renamed::signals")]
+struct Renamed {
+    count: u32,
+    bonus: scoring::Bonus,
+}
+
+#[test]
+fn uses_renamed_crate_paths() {
+    let renamed = Renamed {
+        count: 1,
+        bonus: scoring::Bonus { points: 2 },
+    }
+    .as_mutable_struct();
+    renamed.count.set(3);
+    assert_eq!(block_on(renamed.snapshot_signal().to_stream().next()), Some(Renamed {
+        count: 3,
+        bonus: scoring::Bonus { points: 2 },
+    }));
+}
+
+#[test]
+fn keeps_restricted_field_visibilities() {
+    let item = inventory::rope();
+    item.name.set("Long rope".to_string());
+    assert_eq!(block_on(item.name_signal_cloned().to_stream().next()), Some("Long rope".to_string()));
+    assert_eq!(inventory::total_weight(&item), 1.5);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct Release {
+    pub version: u32,
+}
+
+#[test]
+fn derives_non_exhaustive_structs() {
+    let release = Release { version: 1 }.as_mutable_struct();
+    release.update(Release { version: 2 });
+    assert_eq!(release.snapshot(), Release { version: 2 });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Buffer<const N: usize> {
+    data: [u8; N],
+    len: usize,
+}
+
+#[test]
+fn derives_const_generic_structs() {
+    let buffer = Buffer::<8> {
+        data: [0; 8],
+        len: 0,
+    }
+    .as_mutable_struct();
+    buffer.data.lock_mut()[0] = 7;
+    buffer.len.set(1);
+    assert_eq!(buffer.snapshot(), Buffer {
+        data: [7, 0, 0, 0, 0, 0, 0, 0],
+        len: 1,
+    });
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Download {
+    status: Result<PlayerScore, String>,
+}
+
+#[test]
+fn stores_result_fields_as_values() {
+    let download = Download {
+        status: Err("pending".to_string()),
+    }
+    .as_mutable_struct();
+    let mut statuses = download.status_signal_cloned().to_stream();
+    assert_eq!(block_on(statuses.next()), Some(Err("pending".to_string())));
+
+    let finished = Download {
+        status: Ok(PlayerScore {
+            points: 10,
+            multiplier: 1.0
+        }),
+    };
+    download.update(finished.clone());
+    assert_eq!(block_on(statuses.next()), Some(finished.status.clone()));
+    assert_eq!(download.snapshot(), finished);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct Save {
+    slot: u8,
+    #[signal_lazy] character: Character,
+}
+
+#[test]
+fn builds_lazy_fields_on_first_access() {
+    let save = Save {
+        slot: 1,
+        character: Character {
+            level: 1,
+            name: "Ada".to_string(),
+            best_score: PlayerScore {
+                points: 10,
+                multiplier: 1.0
+            },
+        },
+    };
+    let mutable_save = save.as_mutable_struct();
+    assert!(!mutable_save.character.is_initialized());
+    assert_eq!(mutable_save.snapshot(), save);
+
+    let mut updated = save.clone();
+    updated.character.level = 2;
+    mutable_save.update(updated.clone());
+    assert!(!mutable_save.character.is_initialized());
+    assert_eq!(mutable_save.snapshot(), updated);
+
+    mutable_save.character.best_score.points.set(20);
+    assert!(mutable_save.character.is_initialized());
+    assert_eq!(mutable_save.character.level.get(), 2);
+    assert_eq!(mutable_save.snapshot().character.best_score.points, 20);
+}
+
+#[test]
+fn diffs_and_patches_lazy_fields_without_building_them() {
+    let save = Save {
+        slot: 1,
+        character: Character {
+            level: 1,
+            name: "Ada".to_string(),
+            best_score: PlayerScore {
+                points: 10,
+                multiplier: 1.0
+            },
+        },
+    };
+    let mutable_save = save.as_mutable_struct();
+    let mut other = save.clone();
+    other.character.level = 2;
+    assert_eq!(mutable_save.diff(&other), vec![
+        SaveFieldDiff::Character(vec![CharacterFieldDiff::Level]),
+    ]);
+
+    mutable_save.apply_diff(ApplyableSave {
+        slot: None,
+        character: Some(ApplyableCharacter {
+            level: Some(3),
+            name: None,
+            best_score: None,
+        }),
+    });
+    assert!(!mutable_save.character.is_initialized());
+    assert_eq!(mutable_save.snapshot().character.level, 3);
+    assert_eq!(mutable_save.snapshot().character.name, "Ada");
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_key = "r#type"]
+struct Tile {
+    #[signal_rename = "kind"] r#type: String,
+    #[signal_rename = "position"] coordinate: Coordinate,
+}
+
+#[test]
+fn renames_mutable_fields() {
+    let tile = Tile {
+        r#type: "grass".to_string(),
+        coordinate: Coordinate { x: 1, y: 2 },
+    };
+    let mutable_tile = tile.as_mutable_struct();
+    assert_eq!(mutable_tile.kind.get_cloned(), "grass");
+    let mut kinds = mutable_tile.kind_signal_cloned().to_stream();
+    assert_eq!(block_on(kinds.next()), Some("grass".to_string()));
+
+    mutable_tile.kind.set("water".to_string());
+    mutable_tile.position().x.set(3);
+    assert_eq!(block_on(kinds.next()), Some("water".to_string()));
+    assert_eq!(mutable_tile.snapshot(), Tile {
+        r#type: "water".to_string(),
+        coordinate: Coordinate { x: 3, y: 2 },
+    });
+
+    mutable_tile.update(tile.clone());
+    assert_eq!(mutable_tile.snapshot(), tile);
+    assert_eq!(MutableTile::field_names(), &["kind", "position"]);
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+struct CacheEntry {
+    created: std::time::Instant,
+    ttl: Duration,
+}
+
+#[test]
+fn stores_time_fields_as_values() {
+    let created = std::time::Instant::now();
+    let entry = CacheEntry {
+        created,
+        ttl: Duration::from_secs(60),
+    }
+    .as_mutable_struct();
+    let mut ttls = entry.ttl_signal().to_stream();
+    assert_eq!(block_on(ttls.next()), Some(Duration::from_secs(60)));
+
+    entry.ttl.set(Duration::from_secs(5));
+    entry.created.set(created + Duration::from_secs(1));
+    assert_eq!(block_on(ttls.next()), Some(Duration::from_secs(5)));
+    assert_eq!(entry.snapshot(), CacheEntry {
+        created: created + Duration::from_secs(1),
+        ttl: Duration::from_secs(5),
+    });
+}
+
+macro_rules! declare_counter {
+    ($name:ident, $mutable_name:literal, $($field:ident: $ty:ty),*) => {
+        /// A counter declared by a macro.
+        #[derive(AsMutableStruct, Debug, PartialEq, Clone, Serialize)]
+        #[MutableStructName = $mutable_name]
+        #[cfg_attr(test, signal_json)]
+        struct $name {
+            $($field: $ty),*
+        }
+    };
+}
+
+declare_counter!(Counter, "CounterState", count: u32, limit: Option<u32>, history: Vec<u32>);
+
+#[test]
+fn derives_structs_declared_by_macros() {
+    let counter = CounterState::from_snapshot(&Counter {
+        count: 1,
+        limit: Some(3),
+        history: vec![0],
+    });
+    let _: &Mutable<Option<u32>> = &counter.limit;
+    let _: &MutableVec<u32> = &counter.history;
+
+    counter.count.set(2);
+    counter.limit.set(None);
+    counter.history.lock_mut().push(1);
+    assert_eq!(counter.snapshot(), Counter {
+        count: 2,
+        limit: None,
+        history: vec![0, 1],
+    });
+    assert_eq!(counter.snapshot_json(), serde_json::json!({
+        "count": 2,
+        "limit": null,
+        "history": [0, 1],
+    }));
+}
+
+#[derive(AsMutableStruct, Debug, PartialEq, Clone)]
+#[signal_assert_thread_safe]
+struct SharedScore {
+    points: u32,
+    players: Vec<String>,
+}
+
+#[test]
+fn sends_thread_safe_structs_to_other_threads() {
+    let score = SharedScore {
+        points: 1,
+        players: vec!["Ada".to_string()],
+    }
+    .as_mutable_struct();
+    let handle = std::thread::spawn(move || {
+        score.points.set(2);
+        score.snapshot()
+    });
+    assert_eq!(handle.join().unwrap(), SharedScore {
+        points: 2,
+        players: vec!["Ada".to_string()],
+    });
+}
@@ -0,0 +1,11 @@
+use futures_signals_structs::AsMutableStruct;
+use std::rc::Rc;
+
+#[derive(AsMutableStruct)]
+#[signal_assert_thread_safe]
+struct SharedScore {
+    points: u32,
+    #[signal_skip] owner: Rc<String>,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(Debug, PartialEq)]
+struct Handle;
+
+#[derive(AsMutableStruct)]
+struct Connection {
+    #[signal_basic] handle: Handle,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+enum Direction {
+    Up,
+    Down,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use futures_signals_structs::{AsMutableStruct, MutableStructVec};
+
+#[derive(AsMutableStruct, Clone, PartialEq)]
+struct PlayerScore {
+    points: u32,
+}
+
+#[derive(AsMutableStruct, Clone, PartialEq)]
+struct Team {
+    #[mutable_type = "MutableStructVec<MutablePlayerScore>"]
+    captain: PlayerScore,
+}
+
+#[derive(AsMutableStruct, Clone, PartialEq)]
+struct Match {
+    #[mutable_type = "PlayerScore"]
+    winner: PlayerScore,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+#[MutableStructName = 5]
+struct PlayerScore {
+    points: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+struct GameScore {
+    #[mutable_type = "Mutable Player Score"]
+    player: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+#[mutable(name = "MyMutableScore", rename_all = "camelCase")]
+struct PlayerScore {
+    points: u32,
+}
+
+fn main() {}
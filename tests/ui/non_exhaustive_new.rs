@@ -0,0 +1,11 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+#[non_exhaustive]
+pub struct PlayerScore {
+    pub points: u32,
+}
+
+fn main() {
+    let _ = MutablePlayerScore::new(5);
+}
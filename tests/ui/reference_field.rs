@@ -0,0 +1,8 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+struct View<'a> {
+    name: &'a str,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+#[signal_crate(traits = "futures_signals_structs_traits", derive = "futures_signals_structs")]
+struct PlayerScore {
+    points: u32,
+}
+
+fn main() {}
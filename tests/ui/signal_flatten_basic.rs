@@ -0,0 +1,8 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+struct PlayerScore {
+    #[signal_flatten] points: u32,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+#[signal_transparent]
+struct Size(f32, f32);
+
+fn main() {}
@@ -0,0 +1,15 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct, Clone)]
+struct Address {
+    street: String,
+    city: String,
+}
+
+#[derive(AsMutableStruct)]
+struct Contact {
+    #[signal_snapshot_flatten = "address: Address"] street: String,
+    #[signal_snapshot_flatten = "address: Location"] city: String,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use futures_signals_structs::AsMutableStruct;
+
+fn parse_count(count: String) -> u32 {
+    count.parse().unwrap_or(0)
+}
+
+fn format_count(count: &u32) -> String {
+    count.to_string()
+}
+
+#[derive(AsMutableStruct)]
+struct Inventory {
+    #[signal_update_with = "parse_count"]
+    #[signal_snapshot_with = "format_count"]
+    count: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use futures_signals_structs::AsMutableStruct;
+
+#[derive(AsMutableStruct)]
+union Bits {
+    signed: i32,
+    unsigned: u32,
+}
+
+fn main() {}
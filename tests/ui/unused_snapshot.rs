@@ -0,0 +1,16 @@
+#![deny(unused_must_use)]
+
+use futures_signals_structs::{AsMutableStruct, Diff, MutableStruct};
+
+#[derive(AsMutableStruct, Clone, PartialEq)]
+struct PlayerScore {
+    points: u32,
+}
+
+fn main() {
+    let player_score = PlayerScore { points: 40 };
+    let mutable_score = player_score.as_mutable_struct();
+    mutable_score.snapshot();
+    mutable_score.diff(&player_score);
+    mutable_score.replace_with(player_score.clone());
+}